@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// Builds the indices of a BGZF output as it is written: a `.gzi` of where
+/// each block starts, as written by `bgzip -i`, and for FASTA a `.fai` of
+/// where each sequence starts, as written by `samtools faidx`. Together
+/// they allow random access to the compressed output.
+pub struct BgzfIndexer {
+    path: String,
+    compressed_offset: u64,
+    uncompressed_offset: u64,
+    block_offsets: Vec<(u64, u64)>,
+    fai: Option<FaiIndexer>,
+}
+
+impl BgzfIndexer {
+    /// An indexer for the BGZF file at `path`, which also indexes its
+    /// sequences if it is FASTA.
+    pub fn new(path: &str, fasta: bool) -> BgzfIndexer {
+        BgzfIndexer {
+            path: path.to_string(),
+            compressed_offset: 0,
+            uncompressed_offset: 0,
+            block_offsets: vec![],
+            fai: match fasta {
+                true => Some(FaiIndexer::default()),
+                false => None,
+            },
+        }
+    }
+
+    /// Record a block as it is written, given its uncompressed contents and
+    /// its compressed size.
+    pub fn add_block(&mut self, block: &[u8], compressed_size: usize) {
+        // The first block always starts at the start, so is not recorded
+        if self.compressed_offset > 0 {
+            self.block_offsets.push((self.compressed_offset, self.uncompressed_offset));
+        }
+        self.compressed_offset += compressed_size as u64;
+        self.uncompressed_offset += block.len() as u64;
+        if let Some(ref mut fai) = self.fai {
+            fai.update(block);
+        }
+    }
+
+    /// Write the `.gzi` and any `.fai` alongside the output.
+    pub fn write_files(&mut self) -> io::Result<()> {
+        let mut gzi = BufWriter::new(File::create(format!("{}.gzi", self.path))?);
+        gzi.write_all(&(self.block_offsets.len() as u64).to_le_bytes())?;
+        for (compressed, uncompressed) in &self.block_offsets {
+            gzi.write_all(&compressed.to_le_bytes())?;
+            gzi.write_all(&uncompressed.to_le_bytes())?;
+        }
+        gzi.flush()?;
+
+        let path = &self.path;
+        if let Some(ref mut fai) = self.fai {
+            let entries = fai.finish().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!(
+                "Unable to write a .fai index of {}: {}", path, e)))?;
+            let mut writer = BufWriter::new(File::create(format!("{}.fai", path))?);
+            for entry in entries {
+                writeln!(writer, "{}\t{}\t{}\t{}\t{}",
+                         entry.name, entry.length, entry.offset, entry.line_bases, entry.line_width)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// One line of a `.fai`: a sequence's name and length, the uncompressed
+/// offset of its first base, and the number of bases and bytes in each of
+/// its lines.
+struct FaiEntry {
+    name: String,
+    length: u64,
+    offset: u64,
+    line_bases: u64,
+    line_width: u64,
+}
+
+// Reads FASTA a chunk at a time, noting where each sequence starts and how
+// its lines are laid out.
+#[derive(Default)]
+struct FaiIndexer {
+    entries: Vec<FaiEntry>,
+    offset: u64,
+    in_header: bool,
+    name: Vec<u8>,
+    name_finished: bool,
+    line_length: u64,
+    num_lines: u64,
+    // Whether a line shorter than the first has been seen, which must be
+    // the last line of the sequence
+    short_line_seen: bool,
+    error: Option<String>,
+}
+
+impl FaiIndexer {
+    fn update(&mut self, data: &[u8]) {
+        for b in data {
+            self.offset += 1;
+            if self.in_header {
+                match *b {
+                    b'\n' => {
+                        self.in_header = false;
+                        self.start_sequence();
+                    },
+                    b if b.is_ascii_whitespace() => self.name_finished = true,
+                    b if !self.name_finished => self.name.push(b),
+                    _ => {},
+                }
+            } else if *b == b'>' && self.line_length == 0 {
+                self.in_header = true;
+                self.name.clear();
+                self.name_finished = false;
+            } else if *b == b'\n' {
+                self.end_line();
+            } else {
+                self.line_length += 1;
+            }
+        }
+    }
+
+    fn start_sequence(&mut self) {
+        self.entries.push(FaiEntry {
+            name: String::from_utf8_lossy(&self.name).into_owned(),
+            length: 0,
+            offset: self.offset,
+            line_bases: 0,
+            line_width: 0,
+        });
+        self.num_lines = 0;
+        self.short_line_seen = false;
+    }
+
+    fn end_line(&mut self) {
+        let line_length = self.line_length;
+        self.line_length = 0;
+        let entry = match self.entries.last_mut() {
+            Some(entry) => entry,
+            None => return,
+        };
+        if self.num_lines == 0 {
+            entry.line_bases = line_length;
+            entry.line_width = line_length + 1;
+        } else if self.short_line_seen || line_length > entry.line_bases {
+            if self.error.is_none() {
+                self.error = Some(format!("sequence '{}' has lines of differing lengths", entry.name));
+            }
+        } else if line_length < entry.line_bases {
+            self.short_line_seen = true;
+        }
+        entry.length += line_length;
+        self.num_lines += 1;
+    }
+
+    fn finish(&mut self) -> Result<&[FaiEntry], String> {
+        // A final line without a newline
+        if self.line_length > 0 {
+            self.end_line();
+        }
+        match self.error {
+            Some(ref e) => Err(e.clone()),
+            None => Ok(&self.entries),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::mem;
+use std::slice;
+
+// Empty slots of the hash table
+const EMPTY: u32 = 0;
+// The most entries a table can hold, since slots store the entry plus one
+pub const MAX_ENTRIES: usize = (u32::MAX - 1) as usize;
+
+/// A set of names, each numbered by the order it was added in. The bytes of
+/// every name are kept one after another in a single arena rather than each
+/// in its own allocation, and found through an open addressing hash table
+/// of entry numbers, so that each name costs little more than its length.
+pub struct NameArena {
+    bytes: Vec<u8>,
+    // The end of each name, relative to the start of its chunk of the arena
+    ends: Vec<u32>,
+    // The first entry and arena offset of each chunk of at most 4 GiB of
+    // names, so that the end of each name takes 4 bytes rather than 8
+    chunks: Vec<(usize, usize)>,
+    // Entry plus one, or EMPTY
+    slots: Vec<u32>,
+    hasher: RandomState,
+    // The most memory used at once so far, which is briefly more than is
+    // used afterwards whenever the table grows
+    peak_bytes: usize,
+}
+
+impl NameArena {
+    pub fn new() -> NameArena {
+        NameArena {
+            bytes: vec![],
+            ends: vec![],
+            chunks: vec![(0, 0)],
+            slots: vec![EMPTY; 16],
+            hasher: RandomState::new(),
+            peak_bytes: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn name(&self, entry: usize) -> &str {
+        // Names are only ever added as str, so are valid UTF-8
+        std::str::from_utf8(self.entry_bytes(entry)).unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        match self.slots[self.find_slot(name.as_bytes())] {
+            EMPTY => None,
+            slot => Some(slot as usize - 1),
+        }
+    }
+
+    /// Add a name unless it is already present, returning its entry and
+    /// whether it was added. The caller checks the number of entries stays
+    /// below MAX_ENTRIES.
+    pub fn insert(&mut self, name: &str) -> (usize, bool) {
+        let slot = self.find_slot(name.as_bytes());
+        if self.slots[slot] != EMPTY {
+            return (self.slots[slot] as usize - 1, false)
+        }
+        let entry = self.len();
+        if self.bytes.len() + name.len() - self.chunks[self.chunks.len() - 1].1 > u32::MAX as usize {
+            self.chunks.push((entry, self.bytes.len()));
+        }
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.ends.push((self.bytes.len() - self.chunks[self.chunks.len() - 1].1) as u32);
+        self.slots[slot] = entry as u32 + 1;
+        // Keep the table at most three quarters full
+        if (entry + 1) * 4 > self.slots.len() * 3 {
+            self.grow();
+        }
+        (entry, true)
+    }
+
+    /// The memory used by the arena and table.
+    pub fn heap_bytes(&self) -> usize {
+        self.bytes.capacity()
+            + self.ends.capacity() * mem::size_of::<u32>()
+            + self.chunks.capacity() * mem::size_of::<(usize, usize)>()
+            + self.slots.capacity() * mem::size_of::<u32>()
+    }
+
+    /// The most memory used at once beyond what is used now, while the
+    /// table was growing.
+    pub fn growth_bytes(&self) -> usize {
+        self.peak_bytes.saturating_sub(self.heap_bytes())
+    }
+
+    // The slot holding the name, or the empty slot it would be put in
+    fn find_slot(&self, name: &[u8]) -> usize {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.hasher.hash_one(name) as usize & mask;
+        loop {
+            match self.slots[slot] {
+                EMPTY => return slot,
+                s if self.entry_bytes(s as usize - 1) == name => return slot,
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    fn entry_bytes(&self, entry: usize) -> &[u8] {
+        let chunk = self.chunks.partition_point(|c| c.0 <= entry) - 1;
+        let (first, base) = self.chunks[chunk];
+        let start = match entry == first {
+            true => base,
+            false => base + self.ends[entry - 1] as usize,
+        };
+        &self.bytes[start..base + self.ends[entry] as usize]
+    }
+
+    fn grow(&mut self) {
+        let new_slots = vec![EMPTY; self.slots.len() * 2];
+        let old_slots = mem::replace(&mut self.slots, new_slots);
+        self.peak_bytes = self.peak_bytes.max(self.heap_bytes() + old_slots.capacity() * mem::size_of::<u32>());
+        let mask = self.slots.len() - 1;
+        for entry in old_slots.into_iter().filter(|s| *s != EMPTY) {
+            let mut slot = self.hasher.hash_one(self.entry_bytes(entry as usize - 1)) as usize & mask;
+            while self.slots[slot] != EMPTY {
+                slot = (slot + 1) & mask;
+            }
+            self.slots[slot] = entry;
+        }
+    }
+}
+
+/// The new names of entries in the lists which rename them. Each distinct
+/// new name is stored once in an arena, since many entries are usually
+/// given the same one, e.g. a bin name, and the entry and list it applies
+/// to are kept as a sorted vector of numbers rather than a hash map.
+pub struct Renames {
+    new_names: NameArena,
+    // (entry, list, new name), sorted once every list is read
+    renames: Vec<(u32, u32, u32)>,
+}
+
+impl Renames {
+    pub fn new() -> Renames {
+        Renames { new_names: NameArena::new(), renames: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// Rename an entry in list `list`, which it has not yet been renamed in.
+    /// The caller checks the number of distinct new names stays below
+    /// MAX_ENTRIES.
+    pub fn insert(&mut self, entry: usize, list: usize, new_name: &str) {
+        let (new_name, _) = self.new_names.insert(new_name);
+        self.renames.push((entry as u32, list as u32, new_name as u32));
+    }
+
+    pub fn num_new_names(&self) -> usize {
+        self.new_names.len()
+    }
+
+    /// Sort the renames so they can be looked up.
+    pub fn finish(&mut self) {
+        self.renames.sort_unstable();
+        self.renames.shrink_to_fit();
+    }
+
+    /// The new name of an entry in list `list`, if it has one.
+    pub fn get(&self, entry: usize, list: usize) -> Option<&str> {
+        self.renames.binary_search_by_key(&(entry as u32, list as u32), |r| (r.0, r.1)).ok()
+            .map(|i| self.new_names.name(self.renames[i].2 as usize))
+    }
+
+    pub fn heap_bytes(&self) -> usize {
+        self.new_names.heap_bytes() + self.renames.capacity() * mem::size_of::<(u32, u32, u32)>()
+    }
+
+    pub fn growth_bytes(&self) -> usize {
+        self.new_names.growth_bytes()
+    }
+}
+
+// Marks the lists of an entry as spilled, with the rest of the value the
+// index of its spilled lists
+const SPILLED: u32 = 1 << 31;
+// The most lists kept in a spilled vector before switching to a bitset
+const MAX_FEW_LISTS: usize = 8;
+// The most lists an entry can be in
+pub const MAX_LISTS: usize = (SPILLED - 1) as usize;
+
+enum SpilledLists {
+    Few(Vec<u32>),
+    // Bit i is set if the entry is in list i
+    Many(Vec<u64>),
+}
+
+/// The lists each entry of a name index came from. Almost every name is in
+/// just one list, which is stored inline, while the lists of names in more
+/// than one are spilled into a small vector, or a bitset once there are many.
+pub struct EntryLists {
+    lists: Vec<u32>,
+    spilled: Vec<SpilledLists>,
+}
+
+impl EntryLists {
+    pub fn new() -> EntryLists {
+        EntryLists { lists: vec![], spilled: vec![] }
+    }
+
+    /// Add an entry in the single list `list`, which is less than MAX_LISTS.
+    pub fn push(&mut self, list: usize) {
+        self.lists.push(list as u32);
+    }
+
+    /// Add a list to an entry, returning false if it was already there.
+    pub fn insert(&mut self, entry: usize, list: usize) -> bool {
+        if self.contains(entry, list) {
+            return false
+        }
+        let current = self.lists[entry];
+        if current & SPILLED == 0 {
+            self.lists[entry] = self.spilled.len() as u32 | SPILLED;
+            self.spilled.push(SpilledLists::Few(vec![current, list as u32]));
+            return true
+        }
+        let spilled = &mut self.spilled[(current & !SPILLED) as usize];
+        let mut bits = match *spilled {
+            SpilledLists::Few(ref mut few) if few.len() < MAX_FEW_LISTS => {
+                few.push(list as u32);
+                return true
+            },
+            SpilledLists::Few(ref few) => {
+                let mut bits = vec![];
+                for l in few {
+                    set_bit(&mut bits, *l as usize);
+                }
+                bits
+            },
+            SpilledLists::Many(ref mut bits) => mem::take(bits),
+        };
+        set_bit(&mut bits, list);
+        *spilled = SpilledLists::Many(bits);
+        true
+    }
+
+    pub fn contains(&self, entry: usize, list: usize) -> bool {
+        let current = self.lists[entry];
+        if current & SPILLED == 0 {
+            return current as usize == list
+        }
+        match self.spilled[(current & !SPILLED) as usize] {
+            SpilledLists::Few(ref few) => few.contains(&(list as u32)),
+            SpilledLists::Many(ref bits) => bits.get(list / 64).is_some_and(|w| w & (1 << (list % 64)) != 0),
+        }
+    }
+
+    /// The lists of an entry.
+    pub fn iter(&self, entry: usize) -> ListIter<'_> {
+        let current = self.lists[entry];
+        if current & SPILLED == 0 {
+            return ListIter::One(Some(current as usize))
+        }
+        match self.spilled[(current & !SPILLED) as usize] {
+            SpilledLists::Few(ref few) => ListIter::Few(few.iter()),
+            SpilledLists::Many(ref bits) => ListIter::Many { bits, word: 0, remaining: bits.first().copied().unwrap_or(0) },
+        }
+    }
+
+    /// The memory used by the lists of every entry.
+    pub fn heap_bytes(&self) -> usize {
+        self.lists.capacity() * mem::size_of::<u32>()
+            + self.spilled.capacity() * mem::size_of::<SpilledLists>()
+            + self.spilled.iter().map(|s| match *s {
+                SpilledLists::Few(ref few) => few.capacity() * mem::size_of::<u32>(),
+                SpilledLists::Many(ref bits) => bits.capacity() * mem::size_of::<u64>(),
+            }).sum::<usize>()
+    }
+}
+
+fn set_bit(bits: &mut Vec<u64>, i: usize) {
+    if bits.len() <= i / 64 {
+        bits.resize(i / 64 + 1, 0);
+    }
+    bits[i / 64] |= 1 << (i % 64);
+}
+
+pub enum ListIter<'a> {
+    One(Option<usize>),
+    Few(slice::Iter<'a, u32>),
+    Many { bits: &'a [u64], word: usize, remaining: u64 },
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match *self {
+            ListIter::One(ref mut list) => list.take(),
+            ListIter::Few(ref mut few) => few.next().map(|l| *l as usize),
+            ListIter::Many { bits, ref mut word, ref mut remaining } => {
+                while *remaining == 0 {
+                    *word += 1;
+                    *remaining = *bits.get(*word)?;
+                }
+                let bit = remaining.trailing_zeros() as usize;
+                *remaining &= *remaining - 1;
+                Some(*word * 64 + bit)
+            },
+        }
+    }
+}
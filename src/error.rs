@@ -0,0 +1,95 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+use std::str;
+
+use seq_io;
+
+/// Everything that can go wrong during a run. Each category exits with its
+/// own code, so that scripts can tell them apart:
+///
+/// | Code | Category                                              |
+/// |------|-------------------------------------------------------|
+/// | 2    | Invalid arguments                                     |
+/// | 3    | Sequences found do not match the name lists           |
+/// | 4    | Failure reading or writing a file                     |
+/// | 5    | Malformed FASTQ/FASTA input or regular expression     |
+/// | 6    | Sequence header or name which is not valid UTF-8      |
+/// | 7    | Name specified twice in one list                      |
+#[derive(Debug)]
+pub enum MfqeError {
+    Argument(String),
+    CountMismatch(String),
+    Io { context: String, source: io::Error },
+    Parse(String),
+    Utf8(String),
+    DuplicateName { name: String, list: String },
+}
+
+pub type Result<T> = result::Result<T, MfqeError>;
+
+impl MfqeError {
+    /// An I/O error, described by what was being done when it occurred.
+    pub fn io<S: Into<String>>(context: S, source: io::Error) -> MfqeError {
+        MfqeError::Io { context: context.into(), source }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            MfqeError::Argument(_) => 2,
+            MfqeError::CountMismatch(_) => 3,
+            MfqeError::Io { .. } => 4,
+            MfqeError::Parse(_) => 5,
+            MfqeError::Utf8(_) => 6,
+            MfqeError::DuplicateName { .. } => 7,
+        }
+    }
+}
+
+impl fmt::Display for MfqeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MfqeError::Argument(ref message) |
+            MfqeError::CountMismatch(ref message) |
+            MfqeError::Parse(ref message) |
+            MfqeError::Utf8(ref message) => write!(f, "{}", message),
+            MfqeError::Io { ref context, ref source } => write!(f, "{}: {}", context, source),
+            MfqeError::DuplicateName { ref name, ref list } => write!(
+                f, "It appears that read '{}' was specified twice in input file {}", name, list),
+        }
+    }
+}
+
+impl error::Error for MfqeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            MfqeError::Io { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// The ID of a sequence, given the result of decoding it, or a UTF-8 error
+/// mentioning the format of the input.
+pub fn utf8_id<'a>(id: result::Result<&'a str, str::Utf8Error>, format: &str) -> Result<&'a str> {
+    id.map_err(|_| MfqeError::Utf8(format!("UTF8 error when decoding {} header", format)))
+}
+
+impl From<seq_io::fastq::Error> for MfqeError {
+    fn from(e: seq_io::fastq::Error) -> MfqeError {
+        match e {
+            seq_io::fastq::Error::Io(source) => MfqeError::io("Failed to read FASTQ input", source),
+            e => MfqeError::Parse(format!("Failed to parse FASTQ input: {}", e)),
+        }
+    }
+}
+
+impl From<seq_io::fasta::Error> for MfqeError {
+    fn from(e: seq_io::fasta::Error) -> MfqeError {
+        match e {
+            seq_io::fasta::Error::Io(source) => MfqeError::io("Failed to read FASTA input", source),
+            e => MfqeError::Parse(format!("Failed to parse FASTA input: {}", e)),
+        }
+    }
+}
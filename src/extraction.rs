@@ -0,0 +1,674 @@
+use std::collections::HashSet;
+use std::io::prelude::*;
+use std::path::Path;
+
+use error::{MfqeError, Result};
+use input::{self, SequenceFormat};
+use name_index::{MatchMode, NameIndex, generate_name_index, generate_name_index_from_table};
+use normalise::NameNormaliser;
+use output::{open_output, OutputCompression, OutputOptions, OutputWriter, SequenceOutput};
+use paired;
+use pipeline::{self, RecordOptions};
+use report::{self, ExtractionSummary, OnMissing, ReportFormat};
+
+/// Where the sequences of a job are read from. A path of None means STDIN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobInput {
+    /// FASTQ or FASTA, detected from the first character
+    Auto(Option<String>),
+    Fastq(Option<String>),
+    Fasta(Option<String>),
+    /// Separate R1 and R2 files
+    PairedFastq(String, String),
+    /// Each read followed by its mate
+    InterleavedFastq(Option<String>),
+}
+
+// Opened inputs, ready to be read
+enum InputReaders {
+    Single(SequenceFormat, Box<dyn Read>),
+    Paired(Box<dyn Read>, Box<dyn Read>),
+    Interleaved(Box<dyn Read>),
+}
+
+impl JobInput {
+    /// A description of the input for logs and reports: its path, both
+    /// paths separated by a comma for paired input, or - for STDIN.
+    pub fn name(&self) -> String {
+        match *self {
+            JobInput::Auto(ref path) | JobInput::Fastq(ref path) | JobInput::Fasta(ref path) |
+            JobInput::InterleavedFastq(ref path) => path.as_deref().unwrap_or("-").to_string(),
+            JobInput::PairedFastq(ref path1, ref path2) => format!("{},{}", path1, path2),
+        }
+    }
+
+    fn is_paired(&self) -> bool {
+        match *self {
+            JobInput::PairedFastq(..) | JobInput::InterleavedFastq(_) => true,
+            JobInput::Auto(_) | JobInput::Fastq(_) | JobInput::Fasta(_) => false,
+        }
+    }
+
+    // Open the input, detecting its format if need be. Empty input in an
+    // unknown format is read as `empty_format`.
+    fn open(&self, empty_format: SequenceFormat) -> Result<InputReaders> {
+        Ok(match *self {
+            JobInput::PairedFastq(ref path1, ref path2) => InputReaders::Paired(
+                input::open_input(Some(path1))?, input::open_input(Some(path2))?),
+            JobInput::InterleavedFastq(ref path) => InputReaders::Interleaved(
+                input::open_input(path.as_deref())?),
+            JobInput::Fastq(ref path) => InputReaders::Single(
+                SequenceFormat::Fastq, input::open_input(path.as_deref())?),
+            JobInput::Fasta(ref path) => InputReaders::Single(
+                SequenceFormat::Fasta, input::open_input(path.as_deref())?),
+            JobInput::Auto(ref path) => {
+                let (detected, reader) = input::detect_format(input::open_input(path.as_deref())?)?;
+                InputReaders::Single(detected.unwrap_or(empty_format), reader)
+            },
+        })
+    }
+
+    /// Whether any sequences are read from STDIN.
+    pub fn reads_stdin(&self) -> bool {
+        match *self {
+            JobInput::Auto(ref path) | JobInput::Fastq(ref path) | JobInput::Fasta(ref path) |
+            JobInput::InterleavedFastq(ref path) => path.is_none(),
+            JobInput::PairedFastq(..) => false,
+        }
+    }
+}
+
+/// A single pass over one or more inputs, writing the sequences of each name list to
+/// its output(s). Built with `ExtractionJob::builder()`.
+#[derive(Debug, Clone)]
+pub struct ExtractionJob {
+    // Read one after another, all single-end, all paired or all interleaved
+    inputs: Vec<JobInput>,
+    include_lists: Vec<String>,
+    exclude_lists: Vec<String>,
+    // Names and labels, used instead of lists
+    assignment_table: Option<String>,
+    // The output file(s) of each list, two per list when mates are split
+    index_to_outputs: Vec<Vec<String>>,
+    // The path of the output(s) of each list or label, if not given
+    output_template: Option<String>,
+    unmatched_output: Option<String>,
+    // The format of every output, overriding that implied by its path
+    output_format: Option<SequenceFormat>,
+    match_mode: MatchMode,
+    normaliser: NameNormaliser,
+    record_options: RecordOptions,
+    output_options: OutputOptions,
+    report: Option<(String, ReportFormat)>,
+    missing_names_dir: Option<String>,
+    on_missing: OnMissing,
+}
+
+pub struct ExtractionJobBuilder {
+    job: ExtractionJob,
+    outputs: Vec<String>,
+    outputs2: Option<Vec<String>>,
+}
+
+impl ExtractionJob {
+    /// A builder for a job reading STDIN in whichever format it is, with
+    /// outputs in the format and compression implied by their paths, or
+    /// otherwise the same format gzip compressed, which fails if any name is
+    /// not found.
+    pub fn builder() -> ExtractionJobBuilder {
+        ExtractionJobBuilder {
+            job: ExtractionJob {
+                inputs: vec![JobInput::Auto(None)],
+                include_lists: vec![],
+                exclude_lists: vec![],
+                assignment_table: None,
+                index_to_outputs: vec![],
+                output_template: None,
+                unmatched_output: None,
+                output_format: None,
+                match_mode: MatchMode::Id,
+                normaliser: NameNormaliser::default(),
+                record_options: RecordOptions::default(),
+                output_options: OutputOptions::default(),
+                report: None,
+                missing_names_dir: None,
+                on_missing: OnMissing::Error,
+            },
+            outputs: vec![],
+            outputs2: None,
+        }
+    }
+
+    /// All lists in name index order, i.e. inclusion lists then exclusion
+    /// lists.
+    pub fn lists(&self) -> Vec<&str> {
+        self.include_lists.iter().chain(self.exclude_lists.iter()).map(|l| l.as_str()).collect()
+    }
+
+    /// Read the name lists, extract the sequences and write any report. If
+    /// names are not found, this fails with MfqeError::CountMismatch when the
+    /// job's OnMissing policy is Error, after all output has been written.
+    pub fn run(&self) -> Result<ExtractionSummary> {
+        let (name_index, lists, index_to_outputs) = self.read_name_index()?;
+        // Every input is opened before any output, so that their formats can
+        // be checked first. Outputs are in the format of the first input,
+        // unless otherwise specified.
+        let empty_format = self.output_format.unwrap_or(SequenceFormat::Fastq);
+        let readers = self.inputs.iter()
+            .map(|i| i.open(empty_format))
+            .collect::<Result<Vec<InputReaders>>>()?;
+        let input_formats: Vec<SequenceFormat> = readers.iter().map(|r| match *r {
+            InputReaders::Single(format, _) => format,
+            InputReaders::Paired(..) | InputReaders::Interleaved(_) => SequenceFormat::Fastq,
+        }).collect();
+        self.check_formats(&index_to_outputs, &input_formats)?;
+
+        let open_outputs = |paths: &mut dyn Iterator<Item=&String>|
+            -> Result<Vec<SequenceOutput<Box<dyn OutputWriter>>>> {
+            paths.map(|p| {
+                let format = self.output_format_of(p, input_formats[0]);
+                Ok(SequenceOutput::new(open_output(p, format, &self.output_options)?, format))
+            }).collect()
+        };
+        let mut outputs1 = open_outputs(&mut index_to_outputs.iter().map(|o| &o[0]))?;
+        let mut outputs2 = match index_to_outputs.first().is_some_and(|o| o.len() == 2) {
+            true => Some(open_outputs(&mut index_to_outputs.iter().map(|o| &o[1]))?),
+            false => None,
+        };
+        let mut unmatched_output = match self.unmatched_output {
+            Some(ref o) => open_outputs(&mut Some(o).into_iter())?.pop(),
+            None => None,
+        };
+
+        let mut summary = ExtractionSummary::new(index_to_outputs.len(), name_index.num_entries());
+        for (input, readers) in self.inputs.iter().zip(readers) {
+            if self.inputs.len() > 1 {
+                info!("Reading input {}", input.name());
+            }
+            let input_summary = match readers {
+                InputReaders::Paired(reader1, reader2) => paired::paired_fastq_pipeline(
+                    reader1,
+                    reader2,
+                    &name_index,
+                    &mut outputs1,
+                    outputs2.as_deref_mut().ok_or_else(|| MfqeError::Argument(
+                        "Paired input requires separate outputs for each mate".to_string()))?,
+                    &self.record_options)?,
+                InputReaders::Interleaved(reader) => paired::interleaved_fastq_pipeline(
+                    reader,
+                    &name_index,
+                    &mut outputs1,
+                    outputs2.as_deref_mut(),
+                    &self.record_options)?,
+                InputReaders::Single(SequenceFormat::Fastq, reader) => pipeline::fastq_pipeline(
+                    reader, &name_index, &mut outputs1, unmatched_output.as_mut(), &self.record_options)?,
+                InputReaders::Single(SequenceFormat::Fasta, reader) => pipeline::fasta_pipeline(
+                    reader, &name_index, &mut outputs1, unmatched_output.as_mut(), &self.record_options)?,
+            };
+            summary.merge(&input.name(), input_summary);
+        }
+        if self.inputs.len() > 1 {
+            info!("Extracted {} of {} records from {} inputs",
+                  summary.total_assigned(), summary.total_input, self.inputs.len());
+        }
+        // Finish the outputs before anything is reported
+        let mut finishing: Vec<(&mut SequenceOutput<_>, &String)> = outputs1.iter_mut()
+            .zip(index_to_outputs.iter().map(|o| &o[0]))
+            .collect();
+        if let Some(ref mut outputs2) = outputs2 {
+            finishing.extend(outputs2.iter_mut().zip(index_to_outputs.iter().map(|o| &o[1])));
+        }
+        if let (Some(output), Some(path)) = (unmatched_output.as_mut(), self.unmatched_output.as_ref()) {
+            finishing.push((output, path));
+        }
+        for (output, path) in finishing {
+            output.writer.finish().map_err(|e| MfqeError::io(format!(
+                "Failed to finish writing output file {}", path), e))?;
+        }
+        drop((outputs1, outputs2, unmatched_output));
+
+        let lists: Vec<&str> = lists.iter().map(|l| l.as_str()).collect();
+        if let Some((ref path, format)) = self.report {
+            let index_to_outputs: Vec<Vec<&str>> = index_to_outputs.iter()
+                .map(|o| o.iter().map(|p| p.as_str()).collect())
+                .collect();
+            report::write_report(
+                path, format, &lists, &index_to_outputs, self.unmatched_output.as_deref(),
+                &name_index, &summary)?;
+        }
+        if let Some(ref dir) = self.missing_names_dir {
+            report::write_missing_names(dir, &lists, &name_index, &summary)?;
+        }
+        if let Some(mismatch) = report::count_mismatch(&lists, &name_index, &summary, self.inputs[0].is_paired()) {
+            match self.on_missing {
+                OnMissing::Error => return Err(MfqeError::CountMismatch(mismatch)),
+                OnMissing::Warn => warn!("{}", mismatch),
+                OnMissing::Ignore => {},
+            }
+        }
+        Ok(summary)
+    }
+
+    // Read the names into an index, returning it with the name of each list,
+    // or the labels of an assignment table, and their outputs.
+    fn read_name_index(&self) -> Result<(NameIndex, Vec<String>, Vec<Vec<String>>)> {
+        match (&self.assignment_table, &self.output_template) {
+            (Some(table), Some(template)) => {
+                let (name_index, labels) = generate_name_index_from_table(
+                    table, self.match_mode, self.normaliser.clone())?;
+                let index_to_outputs = labels.iter()
+                    .map(|label| template_outputs(template, &[("{label}", label.clone())]))
+                    .collect();
+                Ok((name_index, labels, index_to_outputs))
+            },
+            _ => {
+                let include_lists: Vec<&str> = self.include_lists.iter().map(|l| l.as_str()).collect();
+                let exclude_lists: Vec<&str> = self.exclude_lists.iter().map(|l| l.as_str()).collect();
+                let name_index = generate_name_index(
+                    &include_lists, &exclude_lists, self.match_mode, self.normaliser.clone())?;
+                let lists = self.lists().iter().map(|l| l.to_string()).collect();
+                Ok((name_index, lists, self.index_to_outputs.clone()))
+            },
+        }
+    }
+
+    // The format an output is written in: that given for the job, or implied
+    // by its path, or otherwise the same as the (first) input.
+    fn output_format_of(&self, path: &str, input_format: SequenceFormat) -> SequenceFormat {
+        self.output_format
+            .or_else(|| SequenceFormat::from_path(path))
+            .unwrap_or(input_format)
+    }
+
+    // Fail if the outputs cannot be written from inputs of these formats,
+    // before any of them are opened.
+    fn check_formats(&self, index_to_outputs: &[Vec<String>], input_formats: &[SequenceFormat]) -> Result<()> {
+        let fasta_input = input_formats.contains(&SequenceFormat::Fasta);
+        let paths = index_to_outputs.iter().flatten().chain(self.unmatched_output.iter());
+        for path in paths {
+            let output_format = self.output_format_of(path, input_formats[0]);
+            if fasta_input && output_format == SequenceFormat::Fastq {
+                return Err(MfqeError::Argument(format!(
+                    "FASTQ output cannot be written from FASTA input, but {} is FASTQ", path)))
+            }
+        }
+        if fasta_input && self.record_options.min_mean_quality.is_some() {
+            return Err(MfqeError::Argument(
+                "A minimum mean quality can only be applied to FASTQ input".to_string()))
+        }
+        Ok(())
+    }
+}
+
+impl ExtractionJobBuilder {
+    pub fn input(mut self, input: JobInput) -> ExtractionJobBuilder {
+        self.job.inputs = vec![input];
+        self
+    }
+
+    /// Inputs read one after another, as if they were one, e.g. the lanes of
+    /// a sample. They must be all single-end, all paired or all interleaved.
+    pub fn inputs(mut self, inputs: Vec<JobInput>) -> ExtractionJobBuilder {
+        self.job.inputs = inputs;
+        self
+    }
+
+    /// Lists of names whose sequences are written to the corresponding
+    /// outputs.
+    pub fn include_lists<S: AsRef<str>>(mut self, lists: &[S]) -> ExtractionJobBuilder {
+        self.job.include_lists = to_strings(lists);
+        self
+    }
+
+    /// Lists of names whose corresponding outputs receive every sequence
+    /// except those listed. Their outputs follow those of the inclusion
+    /// lists.
+    pub fn exclude_lists<S: AsRef<str>>(mut self, lists: &[S]) -> ExtractionJobBuilder {
+        self.job.exclude_lists = to_strings(lists);
+        self
+    }
+
+    /// A table of sequence names and labels, separated by a tab, used instead
+    /// of lists. Each distinct label is treated as a list, written to the
+    /// output given by the `output_template`.
+    pub fn assignment_table<S: Into<String>>(mut self, table: S) -> ExtractionJobBuilder {
+        self.job.assignment_table = Some(table.into());
+        self
+    }
+
+    /// The path of the output of each list, used instead of `outputs`, with
+    /// {dir} replaced by the directory of the list and {stem} (or
+    /// {list_stem}) by its file name without the extension, e.g.
+    /// out/{stem}.fq.gz. For an assignment table, {label} is replaced by each
+    /// label instead, e.g. bins/{label}.fq.gz. Outputs are split by mate if
+    /// the template contains {mate}, which is replaced by 1 or 2.
+    pub fn output_template<S: Into<String>>(mut self, template: S) -> ExtractionJobBuilder {
+        self.job.output_template = Some(template.into());
+        self
+    }
+
+    /// One output per list, in the same order as the lists.
+    pub fn outputs<S: AsRef<str>>(mut self, outputs: &[S]) -> ExtractionJobBuilder {
+        self.outputs = to_strings(outputs);
+        self.outputs2 = None;
+        self
+    }
+
+    /// Separate outputs for the first and second mates of paired input.
+    pub fn split_outputs<S: AsRef<str>>(mut self, outputs1: &[S], outputs2: &[S]) -> ExtractionJobBuilder {
+        self.outputs = to_strings(outputs1);
+        self.outputs2 = Some(to_strings(outputs2));
+        self
+    }
+
+    /// The format of every output. By default each output is written in the
+    /// format implied by its extension, e.g. FASTA for .fna.gz, or otherwise
+    /// in the same format as the input. FASTQ is converted to FASTA by
+    /// dropping qualities.
+    pub fn output_format(mut self, format: SequenceFormat) -> ExtractionJobBuilder {
+        self.job.output_format = Some(format);
+        self
+    }
+
+    /// Write sequences in none of the lists to this path.
+    pub fn unmatched_output<S: Into<String>>(mut self, path: S) -> ExtractionJobBuilder {
+        self.job.unmatched_output = Some(path.into());
+        self
+    }
+
+    pub fn match_mode(mut self, match_mode: MatchMode) -> ExtractionJobBuilder {
+        self.job.match_mode = match_mode;
+        self
+    }
+
+    pub fn normaliser(mut self, normaliser: NameNormaliser) -> ExtractionJobBuilder {
+        self.job.normaliser = normaliser;
+        self
+    }
+
+    /// Prefix to add to each sequence name.
+    pub fn sequence_prefix<S: Into<String>>(mut self, prefix: S) -> ExtractionJobBuilder {
+        self.job.record_options.header_edit.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Suffix to add to each sequence name, after any mate suffix.
+    pub fn sequence_suffix<S: Into<String>>(mut self, suffix: S) -> ExtractionJobBuilder {
+        self.job.record_options.header_edit.suffix = Some(suffix.into());
+        self
+    }
+
+    /// When a sequence is renamed by a two-column list, put its original ID
+    /// at the start of the comment.
+    pub fn keep_old_names(mut self, keep_old_names: bool) -> ExtractionJobBuilder {
+        self.job.record_options.header_edit.keep_old_name = keep_old_names;
+        self
+    }
+
+    /// Write only the ID of each sequence, dropping the comment after it. By
+    /// default the whole header is kept, with any prefix or suffix added to
+    /// the ID.
+    pub fn drop_comments(mut self, drop_comments: bool) -> ExtractionJobBuilder {
+        self.job.record_options.header_edit.drop_comment = drop_comments;
+        self
+    }
+
+    /// Drop FASTQ records whose mean Phred quality is below this, before any
+    /// conversion to FASTA. Pairs are dropped unless both mates pass.
+    pub fn min_mean_quality(mut self, min_mean_quality: f64) -> ExtractionJobBuilder {
+        self.job.record_options.min_mean_quality = Some(min_mean_quality);
+        self
+    }
+
+    pub fn append(mut self, append: bool) -> ExtractionJobBuilder {
+        self.job.output_options.append = append;
+        self
+    }
+
+    /// Whether outputs are compressed, in the format implied by the
+    /// extension of each or otherwise gzip.
+    pub fn compress(mut self, compress: bool) -> ExtractionJobBuilder {
+        self.job.output_options.compression = match compress {
+            true => None,
+            false => Some(OutputCompression::Uncompressed),
+        };
+        self
+    }
+
+    /// Compress every output in this format, whatever its extension.
+    pub fn compression(mut self, compression: OutputCompression) -> ExtractionJobBuilder {
+        self.job.output_options.compression = Some(compression);
+        self
+    }
+
+    pub fn compression_level(mut self, level: u32) -> ExtractionJobBuilder {
+        self.job.output_options.level = Some(level);
+        self
+    }
+
+    /// Threads used to compress each output. With more than one, blocks of
+    /// gzip output are compressed in parallel as separate gzip members, so
+    /// the output differs from that with one thread but is the same for any
+    /// number of threads. BGZF, zstd and xz are compressed in blocks
+    /// whatever the number of threads.
+    pub fn threads(mut self, threads: usize) -> ExtractionJobBuilder {
+        self.job.output_options.threads = threads;
+        self
+    }
+
+    pub fn report<S: Into<String>>(mut self, path: S, format: ReportFormat) -> ExtractionJobBuilder {
+        self.job.report = Some((path.into(), format));
+        self
+    }
+
+    pub fn missing_names_dir<S: Into<String>>(mut self, dir: S) -> ExtractionJobBuilder {
+        self.job.missing_names_dir = Some(dir.into());
+        self
+    }
+
+    pub fn on_missing(mut self, on_missing: OnMissing) -> ExtractionJobBuilder {
+        self.job.on_missing = on_missing;
+        self
+    }
+
+    /// Check the job is consistent, e.g. that there is an output for each
+    /// list.
+    pub fn build(self) -> Result<ExtractionJob> {
+        let mut job = self.job;
+        let num_lists = job.include_lists.len() + job.exclude_lists.len();
+        let first_input = match job.inputs.first() {
+            Some(input) => input.clone(),
+            None => return Err(MfqeError::Argument("At least one input must be given".to_string())),
+        };
+        let paired_input = first_input.is_paired();
+        let paired_files = matches!(first_input, JobInput::PairedFastq(..));
+        if job.inputs.iter().any(|i| i.is_paired() != paired_input
+                                 || matches!(i, JobInput::PairedFastq(..)) != paired_files) {
+            return Err(MfqeError::Argument(
+                "Inputs must be all single-end, all paired or all interleaved".to_string()))
+        }
+        if job.output_options.threads == 0 {
+            return Err(MfqeError::Argument("At least one thread must be used".to_string()))
+        }
+        match (job.output_options.compression, job.output_options.level) {
+            (Some(OutputCompression::Uncompressed), Some(_)) => return Err(MfqeError::Argument(
+                "A compression level cannot be given for uncompressed output".to_string())),
+            (Some(compression), level) => { compression.level(level)?; },
+            (None, _) => {},
+        }
+        if job.normaliser.strip_after.as_deref() == Some("") {
+            return Err(MfqeError::Argument("The delimiter to strip names after cannot be empty".to_string()))
+        }
+        // Regular expressions are not normalised, other than being matched
+        // case-insensitively, so could never match a shortened ID
+        if job.match_mode == MatchMode::Regex
+            && (job.normaliser.strip_mate_suffix || job.normaliser.strip_after.is_some()) {
+            return Err(MfqeError::Argument(
+                "Names cannot be stripped with the regex match mode, only compared ignoring case".to_string()))
+        }
+        let stdin_inputs = job.inputs.iter().filter(|i| i.reads_stdin()).count();
+        if stdin_inputs > 1 {
+            return Err(MfqeError::Argument("Only one input can be read from STDIN".to_string()))
+        }
+        if paired_input && job.unmatched_output.is_some() {
+            return Err(MfqeError::Argument(
+                "An unmatched output cannot be used with paired input".to_string()))
+        }
+        let stdin_lists = job.lists().iter().chain(job.assignment_table.as_deref().iter())
+            .filter(|l| **l == "-")
+            .count();
+        if stdin_lists > 1 {
+            return Err(MfqeError::Argument("Only one name list can be read from STDIN".to_string()))
+        }
+        if stdin_lists == 1 && stdin_inputs == 1 {
+            return Err(MfqeError::Argument(
+                "A name list cannot be read from STDIN when the sequence input is also STDIN".to_string()))
+        }
+        if let Some(ref template) = job.output_template {
+            if stdin_lists == 1 && job.assignment_table.is_none() {
+                return Err(MfqeError::Argument(
+                    "An output template cannot be used with a name list read from STDIN".to_string()))
+            }
+            if !self.outputs.is_empty() {
+                return Err(MfqeError::Argument(
+                    "An output template cannot be used with output files".to_string()))
+            }
+            match job.assignment_table {
+                Some(_) => check_template(template, TABLE_PLACEHOLDERS)?,
+                None => check_template(template, LIST_PLACEHOLDERS)?,
+            }
+            let split = template.contains("{mate}");
+            if split && !paired_input {
+                return Err(MfqeError::Argument(
+                    "{mate} can only be used in an output template with paired or interleaved input".to_string()))
+            }
+            if !split && paired_files {
+                return Err(MfqeError::Argument(
+                    "The output template must contain {mate} for paired input".to_string()))
+            }
+        }
+        if job.assignment_table.is_some() {
+            if num_lists > 0 {
+                return Err(MfqeError::Argument(
+                    "An assignment table cannot be used with name lists".to_string()))
+            }
+            return match job.output_template {
+                Some(ref template) if template.contains("{label}") => Ok(job),
+                _ => Err(MfqeError::Argument(
+                    "An assignment table requires an output template containing {label}".to_string())),
+            }
+        }
+        if num_lists == 0 {
+            return Err(MfqeError::Argument("At least one name list must be given".to_string()))
+        }
+        let (outputs, outputs2) = match job.output_template {
+            Some(ref template) => {
+                let paths: Vec<Vec<String>> = job.lists().iter()
+                    .map(|l| template_outputs(template, &list_placeholders(l)))
+                    .collect();
+                check_distinct_outputs(&paths)?;
+                let outputs = paths.iter().map(|p| p[0].clone()).collect();
+                let outputs2 = match template.contains("{mate}") {
+                    true => Some(paths.iter().map(|p| p[1].clone()).collect()),
+                    false => None,
+                };
+                (outputs, outputs2)
+            },
+            None => (self.outputs, self.outputs2),
+        };
+        check_output_count(&outputs, num_lists)?;
+        job.index_to_outputs = match outputs2 {
+            Some(outputs2) => {
+                if !paired_input {
+                    return Err(MfqeError::Argument(
+                        "--output-fastq-files-1 and --output-fastq-files-2 can only be used \
+                         with paired (--input-fastq-1/--input-fastq-2) or --interleaved input".to_string()))
+                }
+                check_output_count(&outputs2, num_lists)?;
+                outputs.into_iter().zip(outputs2).map(|(o1, o2)| vec![o1, o2]).collect()
+            },
+            None => {
+                if paired_files {
+                    return Err(MfqeError::Argument(
+                        "Paired input requires separate outputs for each mate".to_string()))
+                }
+                outputs.into_iter().map(|o| vec![o]).collect()
+            },
+        };
+        Ok(job)
+    }
+}
+
+const LIST_PLACEHOLDERS: &[&str] = &["{dir}", "{stem}", "{list_stem}", "{mate}"];
+const TABLE_PLACEHOLDERS: &[&str] = &["{label}", "{mate}"];
+
+// Fail if the template contains a placeholder other than those allowed.
+fn check_template(template: &str, allowed: &[&str]) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let placeholder = match rest[start..].find('}') {
+            Some(end) => &rest[start..start+end+1],
+            None => &rest[start..],
+        };
+        if !allowed.contains(&placeholder) {
+            return Err(MfqeError::Argument(format!(
+                "Unknown placeholder {} in output template {}, expected one of {}",
+                placeholder, template, allowed.join(", "))))
+        }
+        rest = &rest[start+placeholder.len()..];
+    }
+    Ok(())
+}
+
+// The values of the placeholders of an output template for a list: the
+// directory of the list, and its file name without the extension.
+fn list_placeholders(list: &str) -> Vec<(&'static str, String)> {
+    let path = Path::new(list);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    };
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    vec![("{dir}", dir), ("{stem}", stem.clone()), ("{list_stem}", stem)]
+}
+
+// The output(s) of a list or of a label of an assignment table, given the
+// values of the template's placeholders, one for each mate if the template
+// contains {mate}.
+fn template_outputs(template: &str, values: &[(&str, String)]) -> Vec<String> {
+    let path = values.iter().fold(template.to_string(), |path, (placeholder, value)| {
+        path.replace(placeholder, value)
+    });
+    match path.contains("{mate}") {
+        true => vec![path.replace("{mate}", "1"), path.replace("{mate}", "2")],
+        false => vec![path],
+    }
+}
+
+// Fail if an output template gives two lists the same output.
+fn check_distinct_outputs(index_to_outputs: &[Vec<String>]) -> Result<()> {
+    let mut seen = HashSet::new();
+    for path in index_to_outputs.iter().flatten() {
+        if !seen.insert(path) {
+            return Err(MfqeError::Argument(format!(
+                "The output template gives the same output {} for more than one list", path)))
+        }
+    }
+    Ok(())
+}
+
+fn to_strings<S: AsRef<str>>(values: &[S]) -> Vec<String> {
+    values.iter().map(|v| v.as_ref().to_string()).collect()
+}
+
+// check the number of read name files is the same as the number of output
+// files.
+fn check_output_count(output_files: &[String], num_lists: usize) -> Result<()> {
+    if output_files.len() != num_lists {
+        return Err(MfqeError::Argument(format!(
+            "The number of read name files was {}, output files there was \
+             {}. These numbers must be equal.",
+            num_lists, output_files.len())));
+    }
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, Cursor};
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use error::{MfqeError, Result};
+
+/// Compression formats recognised from the first few bytes of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputCompression {
+    Uncompressed,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const LONGEST_MAGIC: usize = 6;
+
+impl InputCompression {
+    pub fn from_magic(magic: &[u8]) -> InputCompression {
+        if magic.starts_with(GZIP_MAGIC) {
+            InputCompression::Gzip
+        } else if magic.starts_with(BZIP2_MAGIC) {
+            InputCompression::Bzip2
+        } else if magic.starts_with(XZ_MAGIC) {
+            InputCompression::Xz
+        } else if magic.starts_with(ZSTD_MAGIC) {
+            InputCompression::Zstd
+        } else {
+            InputCompression::Uncompressed
+        }
+    }
+}
+
+/// Open the given path for reading, or STDIN if no path is given,
+/// transparently decompressing it if it is compressed.
+pub fn open_input(path: Option<&str>) -> Result<Box<dyn Read>> {
+    match path {
+        Some(p) => {
+            let file = File::open(p)
+                .map_err(|e| MfqeError::io(format!("Failed to open input file {} for reading", p), e))?;
+            decompressing_reader(BufReader::new(file))
+                .map_err(|e| MfqeError::io(format!("Failed to read from input file {}", p), e))
+        },
+        None => decompressing_reader(io::stdin())
+            .map_err(|e| MfqeError::io("Failed to read from STDIN", e)),
+    }
+}
+
+/// Wrap a reader in the decoder matching its magic bytes. The peeked bytes
+/// are chained back onto the front of the stream so nothing is lost.
+/// Multi-member gzip (including BGZF), bzip2 and xz streams are read to the
+/// end rather than stopping after the first member.
+pub fn decompressing_reader<R: Read + 'static>(mut reader: R) -> io::Result<Box<dyn Read>> {
+    let mut magic = Vec::with_capacity(LONGEST_MAGIC);
+    (&mut reader).take(LONGEST_MAGIC as u64).read_to_end(&mut magic)?;
+    let compression = InputCompression::from_magic(&magic);
+    debug!("Detected input compression {:?}", compression);
+
+    let stream = Cursor::new(magic).chain(reader);
+    Ok(match compression {
+        InputCompression::Uncompressed => Box::new(stream),
+        InputCompression::Gzip => Box::new(MultiGzDecoder::new(stream)),
+        InputCompression::Bzip2 => Box::new(MultiBzDecoder::new(stream)),
+        InputCompression::Xz => Box::new(XzDecoder::new_multi_decoder(stream)),
+        InputCompression::Zstd => Box::new(ZstdDecoder::new(stream)?),
+    })
+}
+
+/// Sequence file formats, told apart by the first character of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceFormat {
+    Fastq,
+    Fasta,
+}
+
+// Extensions of compressed files, which are ignored when guessing the format
+// of a sequence file from its path
+const COMPRESSION_EXTENSIONS: &[&str] = &[".gz", ".bgz", ".bz2", ".xz", ".zst"];
+
+impl SequenceFormat {
+    /// The format implied by the extension of a path, ignoring any
+    /// compression extension, e.g. FASTA for reads.fna.gz.
+    pub fn from_path(path: &str) -> Option<SequenceFormat> {
+        let lower = path.to_lowercase();
+        let stem = COMPRESSION_EXTENSIONS.iter()
+            .find(|e| lower.ends_with(*e))
+            .map_or(lower.as_str(), |e| &lower[..lower.len() - e.len()]);
+        let extension = stem.rsplit('.').next().unwrap_or("");
+        match extension {
+            "fq" | "fastq" => Some(SequenceFormat::Fastq),
+            "fa" | "fasta" | "fna" | "faa" | "fas" => Some(SequenceFormat::Fasta),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SequenceFormat::Fastq => "FASTQ",
+            SequenceFormat::Fasta => "FASTA",
+        }
+    }
+}
+
+/// Determine whether (decompressed) input is FASTQ or FASTA from its first
+/// non-whitespace byte, returning a reader which yields the input from that
+/// byte on, since the parsers expect a record to start the input. The format
+/// is None if the input is empty.
+pub fn detect_format(mut reader: Box<dyn Read>) -> Result<(Option<SequenceFormat>, Box<dyn Read>)> {
+    let mut byte = [0u8];
+    let format = loop {
+        let read = reader.read(&mut byte)
+            .map_err(|e| MfqeError::io("Failed to read sequence input", e))?;
+        if read == 0 {
+            break None;
+        }
+        match byte[0] {
+            b'@' => break Some(SequenceFormat::Fastq),
+            b'>' => break Some(SequenceFormat::Fasta),
+            b if b.is_ascii_whitespace() => continue,
+            b => return Err(MfqeError::Parse(format!(
+                "Unrecognised sequence input: expected '@' (FASTQ) or '>' (FASTA) \
+                 at the start, but found '{}'", b.escape_ascii()))),
+        }
+    };
+    debug!("Detected input format {:?}", format);
+    let first: Vec<u8> = format.map(|_| byte[0]).into_iter().collect();
+    Ok((format, Box::new(Cursor::new(first).chain(reader))))
+}
@@ -0,0 +1,41 @@
+//! Extract multiple sets of sequences from a FASTQ or FASTA file by name, in
+//! a single pass over the input.
+//!
+//! ```no_run
+//! use mfqe::extraction::{ExtractionJob, JobInput};
+//!
+//! let summary = ExtractionJob::builder()
+//!     .input(JobInput::Fastq(Some("reads.fq.gz".to_string())))
+//!     .include_lists(&["sample1.txt", "sample2.txt"])
+//!     .outputs(&["sample1.fq.gz", "sample2.fq.gz"])
+//!     .build()?
+//!     .run()?;
+//! println!("Wrote {} of {} reads", summary.total_assigned(), summary.total_input);
+//! # Ok::<(), mfqe::error::MfqeError>(())
+//! ```
+
+extern crate seq_io;
+extern crate flate2;
+#[macro_use]
+extern crate log;
+extern crate bzip2;
+extern crate xz2;
+extern crate zstd;
+extern crate regex;
+#[macro_use]
+extern crate serde_json;
+
+pub mod bgzf_index;
+mod compact_index;
+pub mod error;
+pub mod extraction;
+pub mod input;
+pub mod name_index;
+pub mod normalise;
+pub mod output;
+pub mod paired;
+pub mod parallel_gz;
+pub mod pipeline;
+mod prefix_trie;
+pub mod record;
+pub mod report;
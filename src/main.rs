@@ -1,29 +1,24 @@
-use std::io::prelude::*;
-use std::io::{BufReader, BufWriter};
-use std::fs::File;
-use std::fs::OpenOptions;
-use std::collections::{HashMap,HashSet};
 use std::env;
-use std::path::Path;
-use std::str;
-
-extern crate seq_io;
-use seq_io::fastq::Record;
-use seq_io::fasta::Record as OtherRecord;
+use std::process;
 
 extern crate clap;
 use clap::*;
 
-extern crate flate2;
-use flate2::Compression;
-use flate2::write::GzEncoder;
-
 #[macro_use]
 extern crate log;
 extern crate env_logger;
 use log::LevelFilter;
 use env_logger::Builder;
 
+extern crate mfqe;
+use mfqe::error::{MfqeError, Result};
+use mfqe::extraction::{ExtractionJob, JobInput};
+use mfqe::input::SequenceFormat;
+use mfqe::name_index::MatchMode;
+use mfqe::normalise::NameNormaliser;
+use mfqe::output::OutputCompression;
+use mfqe::report::{ExtractionSummary, OnMissing, ReportFormat};
+
 
 fn main() {
     let app = Command::new("mfqe")
@@ -37,13 +32,30 @@ fn main() {
                Extract one or more sets of reads from a FASTQ (or \
                FASTA) file by specifying their read names.\n\n\
 \n\
-               Read name files are uncompressed text files with read names \
-               (without comments).\n\
+               Read name files are text files, optionally compressed, with read names \
+               (without comments), each optionally followed by a tab and a new \
+               name to rename the read to.\n\
 \n\
-               Output is gzip-compressed unless --output-uncompressed is specified, input is uncompressed.\n\
+               Output is compressed as its extension implies (.gz gzip, .bgz BGZF, \
+               .zst zstd, .xz xz, none for e.g. .fq or .fa), otherwise with gzip, \
+               unless --compression or --output-uncompressed is specified. \
+               Input may be uncompressed, or compressed with gzip (including BGZF), \
+               bzip2, xz or zstd, which is detected automatically.\n\
                \nOther FASTQ options:
-               \n--input-fastq <PATH>: Use this file as input FASTQ [default: Use STDIN]\
+               \n--input-fastq <PATH> ..: Use these files as input FASTQ, read one after \
+               another, e.g. the lanes of a sample [default: Use STDIN]\
                \n\n\
+               Alternatively, --input <PATH> (or STDIN) is read as FASTQ or FASTA, \
+               detected from its first character, and each of --output-files is written \
+               as FASTA if its name ends in e.g. .fa or .fasta.gz, as FASTQ if it ends \
+               in e.g. .fq.gz, and otherwise in the same format as the input, unless \
+               --output-format is given. FASTQ is converted to FASTA by dropping \
+               qualities.\n\n\
+               Paired-end reads can be extracted together, keeping mates in sync:\n\n\
+               --input-fastq-1 <R1> --input-fastq-2 <R2>\n\
+               --output-fastq-files-1 <OUTPUT1_R1> .. --output-fastq-files-2 <OUTPUT1_R2> ..\n\n\
+               or from an interleaved FASTQ with --interleaved, writing either \
+               interleaved (--output-fastq-files) or split (--output-fastq-files-1/-2) outputs.\n\n\
                An analogous set of options is implemented for FASTA:\n\n\
                --output-fasta-files <OUTPUT1> ..\n\
                --input-fasta <PATH>\n\n")
@@ -53,8 +65,10 @@ fn main() {
         .arg(Arg::new("sequence-name-lists")
              .long("sequence-name-lists")
              .short('l')
-             .help("List of files each containing sequence IDs")
-             .required_unless_present_any(&["fastq-read-name-lists","fasta-read-name-lists"])
+             .help("List of files each containing sequence IDs, or sequence IDs and \
+                    a tab then the name to rename each sequence to. Files may be \
+                    compressed, and one may be - to read it from STDIN")
+             .required_unless_present_any(["fastq-read-name-lists","fasta-read-name-lists","exclude-lists","assignment-table"])
              .conflicts_with_all(&["fastq-read-name-lists","fasta-read-name-lists"])
              .takes_value(true)
              .multiple_occurrences(true)
@@ -62,7 +76,7 @@ fn main() {
         .arg(Arg::new("fastq-read-name-lists")
              .long("fastq-read-name-lists")
              .help("List of files each containing sequence IDs [alias for --sequence-name-lists]")
-             .required_unless_present_any(&["sequence-name-lists","fasta-read-name-lists"])
+             .required_unless_present_any(["sequence-name-lists","fasta-read-name-lists","exclude-lists","assignment-table"])
              .conflicts_with_all(&["sequence-name-lists","fasta-read-name-lists"])
              .takes_value(true)
              .multiple_occurrences(true)
@@ -70,44 +84,234 @@ fn main() {
         .arg(Arg::new("fasta-read-name-lists")
              .long("fasta-read-name-lists")
              .help("List of files each containing sequence IDs [alias for --sequence-name-lists]")
-             .required_unless_present_any(&["fastq-read-name-lists","sequence-name-lists"])
+             .required_unless_present_any(["fastq-read-name-lists","sequence-name-lists","exclude-lists","assignment-table"])
              .conflicts_with_all(&["fastq-read-name-lists","sequence-name-lists"])
              .takes_value(true)
              .multiple_occurrences(true)
              .multiple_values(true))
+        .arg(Arg::new("exclude-lists")
+             .long("exclude-lists")
+             .help("List of files each containing sequence IDs to exclude. Each \
+                    corresponding output receives every sequence except those \
+                    listed. Outputs are given for the --sequence-name-lists first, \
+                    then for these lists")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+
+        .arg(Arg::new("assignment-table")
+             .long("assignment-table")
+             .help("File of sequence IDs, each followed by a tab and a label, used \
+                    instead of name lists. Sequences of each label are written to \
+                    the --output-template with {label} replaced by the label")
+             .requires("output-template")
+             .conflicts_with_all(&["sequence-name-lists","fastq-read-name-lists","fasta-read-name-lists","exclude-lists"])
+             .takes_value(true))
+        .arg(Arg::new("output-template")
+             .long("output-template")
+             .help("Path of the output of each list, used instead of listing outputs, \
+                    with {dir} replaced by the directory of the list and {stem} (or \
+                    {list_stem}) by its file name without the extension, e.g. \
+                    out/{stem}.fq.gz. With --assignment-table, {label} is replaced \
+                    by each label instead. For paired input, {mate} is replaced by \
+                    1 or 2 to split mates into separate files. Directories are \
+                    created as needed")
+             .conflicts_with_all(&["output-fastq-files","output-fasta-files","output-files","output-fastq-files-1"])
+             .takes_value(true))
 
         .arg(Arg::new("output-fastq-files")
              .long("output-fastq-files")
              .help("List of files to write FASTQ to")
-             .required_unless_present("output-fasta-files")
+             .required_unless_present_any(["output-fasta-files","output-fastq-files-1","output-files","output-template"])
+             .conflicts_with("output-fastq-files-1")
              .takes_value(true)
              .multiple_occurrences(true)
              .multiple_values(true))
         .arg(Arg::new("input-fastq")
              .long("input-fastq")
-             .help("File containing uncompressed input FASTQ sequences [default: Use STDIN]")
-             .takes_value(true))
+             .help("File(s) containing input FASTQ sequences, optionally compressed, \
+                    read one after another [default: Use STDIN]")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+        .arg(Arg::new("input")
+             .long("input")
+             .help("File(s) containing input FASTQ or FASTA sequences, optionally compressed, \
+                    read one after another. The format is detected from the first \
+                    character [default: Use STDIN]")
+             .conflicts_with_all(&["input-fastq","input-fasta","input-fastq-1"])
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+        .arg(Arg::new("output-files")
+             .long("output-files")
+             .help("List of files to write sequences to, in the format implied by each \
+                    file's extension, otherwise in the same format as the input")
+             .conflicts_with_all(&["output-fastq-files","output-fasta-files","output-fastq-files-1"])
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+
+        .arg(Arg::new("input-fastq-1")
+             .long("input-fastq-1")
+             .help("File(s) containing forward (R1) reads of paired input FASTQ")
+             .requires("input-fastq-2")
+             .conflicts_with_all(&["input-fastq","input-fasta","output-fastq-files","output-fasta-files","output-files"])
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+        .arg(Arg::new("input-fastq-2")
+             .long("input-fastq-2")
+             .help("File(s) containing reverse (R2) reads of paired input FASTQ, in \
+                    the same order as the R1 files")
+             .requires("input-fastq-1")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+        .arg(Arg::new("interleaved")
+             .long("interleaved")
+             .help("Input FASTQ is interleaved, with each read followed by its mate. \
+                    Pairs are written interleaved to --output-fastq-files, or split \
+                    with --output-fastq-files-1/--output-fastq-files-2")
+             .conflicts_with_all(&["input-fastq-1","input-fasta"]))
+        .arg(Arg::new("output-fastq-files-1")
+             .long("output-fastq-files-1")
+             .help("List of files to write forward (R1) reads of each pair to")
+             .requires("output-fastq-files-2")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
+        .arg(Arg::new("output-fastq-files-2")
+             .long("output-fastq-files-2")
+             .help("List of files to write reverse (R2) reads of each pair to")
+             .requires("output-fastq-files-1")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
 
         .arg(Arg::new("output-fasta-files")
              .long("output-fasta-files")
-             .help("List of files to write FASTA to")
-             .required_unless_present("output-fastq-files")
+             .help("List of files to write FASTA to, dropping qualities if the input is FASTQ")
+             .required_unless_present_any(["output-fastq-files","output-fastq-files-1","output-files","output-template"])
              .takes_value(true)
              .multiple_occurrences(true)
              .multiple_values(true))
         .arg(Arg::new("input-fasta")
              .long("input-fasta")
-             .help("File containing uncompressed input FASTA sequences [default: Use STDIN]")
-             .takes_value(true))
+             .help("File(s) containing input FASTA sequences, optionally compressed, \
+                    read one after another [default: Use STDIN]")
+             .takes_value(true)
+             .multiple_occurrences(true)
+             .multiple_values(true))
              
+        .arg(Arg::new("output-format")
+             .long("output-format")
+             .help("Format of every output, overriding that implied by their extensions \
+                    [default: from the extension, otherwise the same as the input]")
+             .possible_values(["fastq","fasta"])
+             .conflicts_with_all(&["output-fastq-files","output-fasta-files"])
+             .takes_value(true))
+        .arg(Arg::new("min-mean-quality")
+             .long("min-mean-quality")
+             .help("Do not output FASTQ reads whose mean Phred quality score is below \
+                    this. Pairs are output only if both mates pass. Filtered reads \
+                    still count as found")
+             .takes_value(true))
+
         .arg(Arg::new("output-uncompressed")
              .long("output-uncompressed")
-             .help("Output sequences uncompressed [default: gzip compress outputs]")
+             .help("Output sequences uncompressed [default: compress outputs as \
+                    their extensions imply, otherwise with gzip]")
              .short('u'))
+        .arg(Arg::new("compression")
+             .long("compression")
+             .help("Compression of every output, overriding that implied by their \
+                    extensions. BGZF outputs are indexed in a .gzi, and a .fai for \
+                    FASTA [default: from the extension, otherwise gzip]")
+             .possible_values(["gzip","bgzf","zstd","xz","none"])
+             .conflicts_with("output-uncompressed")
+             .takes_value(true))
+        .arg(Arg::new("compression-level")
+             .long("compression-level")
+             .help("Compression level, 0-9 for gzip, BGZF and xz, or 1-22 for zstd \
+                    [default: 6 for gzip, BGZF and xz, 3 for zstd]")
+             .takes_value(true))
+        .arg(Arg::new("threads")
+             .long("threads")
+             .short('t')
+             .help("Number of threads used to compress each output. With more \
+                    than one, blocks of gzip output are compressed in parallel, as \
+                    pigz does")
+             .default_value("1")
+             .takes_value(true))
         .arg(Arg::new("sequence-prefix")
              .long("sequence-prefix")
              .help("Prefix to add to each sequence name")
              .takes_value(true))
+        .arg(Arg::new("sequence-suffix")
+             .long("sequence-suffix")
+             .help("Suffix to add to each sequence name, after any /1 or /2")
+             .takes_value(true))
+        .arg(Arg::new("keep-old-names")
+             .long("keep-old-names")
+             .help("When sequences are renamed by a two-column list, add their \
+                    original name to the start of the comment"))
+        .arg(Arg::new("drop-comments")
+             .long("drop-comments")
+             .help("Write only the name of each sequence, dropping the comment \
+                    after it [default: Keep the whole header]"))
+        .arg(Arg::new("match-mode")
+             .long("match-mode")
+             .help("How names in the lists are matched against sequence headers: \
+                    'id' matches the ID exactly, 'full-header' the whole header \
+                    line including any comment, 'prefix' matches IDs starting with \
+                    the name and 'regex' treats each name as a regular expression \
+                    matched against the ID")
+             .possible_values(["id","full-header","prefix","regex"])
+             .default_value("id")
+             .takes_value(true))
+        .arg(Arg::new("strip-mate-suffix")
+             .long("strip-mate-suffix")
+             .help("Ignore a trailing /1 or /2 on names in lists and sequence IDs"))
+        .arg(Arg::new("strip-after")
+             .long("strip-after")
+             .help("Ignore everything from the first occurrence of this delimiter \
+                    onwards in names in lists and sequence IDs, e.g. ' ' to drop \
+                    Casava comments with --match-mode full-header")
+             .takes_value(true))
+        .arg(Arg::new("ignore-case")
+             .long("ignore-case")
+             .help("Match names in lists and sequence IDs case-insensitively"))
+        .arg(Arg::new("unmatched-output")
+             .long("unmatched-output")
+             .help("Write every sequence which is in none of the name lists to this file, \
+                    in the same format as the outputs")
+             .conflicts_with_all(&["input-fastq-1","interleaved"])
+             .takes_value(true))
+        .arg(Arg::new("report")
+             .long("report")
+             .help("Write a report of the expected and observed numbers of sequences \
+                    for each list, and totals for the run, to this file")
+             .takes_value(true))
+        .arg(Arg::new("report-format")
+             .long("report-format")
+             .help("Format of the --report [default: tsv if the path ends in .tsv, otherwise json]")
+             .possible_values(["json","tsv"])
+             .requires("report")
+             .takes_value(true))
+        .arg(Arg::new("missing-names-dir")
+             .long("missing-names-dir")
+             .help("Write the names of each list which matched no sequence to \
+                    <DIR>/<LIST FILE NAME>.missing.txt, creating the directory if needed")
+             .takes_value(true))
+        .arg(Arg::new("on-missing")
+             .long("on-missing")
+             .help("What to do when the numbers of sequences found differ from the \
+                    numbers of names in the lists: 'error' exits with code 3 once \
+                    all output is written, 'warn' logs a warning and 'ignore' carries on")
+             .possible_values(["error","warn","ignore"])
+             .default_value("error")
+             .takes_value(true))
         .arg(Arg::new("append")
              .long("append")
              .help("Append to output files [default: Overwrite]")
@@ -125,271 +329,137 @@ fn main() {
         panic!("Failed to set log level - has it been specified multiple times?")
     }
 
-    let output_files: Vec<&str>;
-    let input: Option<BufReader<File>>;
+    if let Err(e) = run(&matches) {
+        error!("{}", e);
+        process::exit(e.exit_code());
+    }
+}
 
-    let doing_fastq = matches.is_present("output-fastq-files");
+fn run(matches: &ArgMatches) -> Result<ExtractionSummary> {
     let read_lists: Vec<&str> = if matches.is_present("fasta-read-name-lists") {
         matches.values_of("fasta-read-name-lists").unwrap().collect()
     } else if matches.is_present("fastq-read-name-lists") {
         matches.values_of("fastq-read-name-lists").unwrap().collect()
-    } else {
+    } else if matches.is_present("sequence-name-lists") {
         matches.values_of("sequence-name-lists").unwrap().collect()
+    } else {
+        vec![]
     };
     debug!("Found readname lists {:#?}", read_lists);
+    let exclude_lists: Vec<&str> = match matches.values_of("exclude-lists") {
+        Some(lists) => lists.collect(),
+        None => vec![]
+    };
+    debug!("Found exclusion lists {:#?}", exclude_lists);
 
-    let appending = matches.is_present("append");
-
-    if doing_fastq {
-        // Doing fastq
-        output_files = matches.values_of("output-fastq-files").unwrap().collect();
-        input = match matches.value_of("input-fastq") {
-            Some(path) => Some(BufReader::new(
-                File::open(path)
-                    .expect("Failed to open fastq file for reading"))),
-            None => None
-        };
-
-    } else {
-        output_files = matches.values_of("output-fasta-files").unwrap().collect();
-        input = match matches.value_of("input-fasta") {
-            Some(path) => Some(BufReader::new(
-                File::open(path)
-                    .expect("Failed to open fasta file for reading"))),
-            None => None
-        };
-
-    }
-
-    // check the number of fastq read name files is the same as the number of
-    // output files.
-    if output_files.len() != read_lists.len() {
-        panic!("The number of read name files was {}, output files there was \
-                {}. These numbers must be equal.",
-               output_files.len(), read_lists.len());
-    }
-
-    let name_index = generate_name_index(read_lists);
-
-    // Open output file as gzipped output
-    let output_compressed = !matches.is_present("output-uncompressed");
-    let uncompressed_outputs: Option<Vec<File>> = if output_compressed {
-        None 
-    } else {
-        Some(output_files.iter().map( |o| {
-            open_a_file(o, appending)
-        }).collect())
+    // Each input option may be given several files, which are read in turn
+    let input_paths = |arg: &str| -> Vec<Option<String>> {
+        match matches.values_of(arg) {
+            Some(paths) => paths.map(|p| Some(p.to_string())).collect(),
+            None => vec![None],
+        }
     };
-    let compressed_outputs: Option<Vec<GzEncoder<BufWriter<File>>>> = if output_compressed {
-        Some(output_files.iter().map( |o| {
-            let w1 = open_a_file(o, appending);
-            GzEncoder::new(BufWriter::new(w1), Compression::default())
-            }).collect())
+    let inputs: Vec<JobInput> = if matches.is_present("input-fastq-1") {
+        let paths1: Vec<&str> = matches.values_of("input-fastq-1").unwrap().collect();
+        let paths2: Vec<&str> = matches.values_of("input-fastq-2").unwrap().collect();
+        if paths1.len() != paths2.len() {
+            return Err(MfqeError::Argument(format!(
+                "The number of R1 input files was {}, but R2 files there were {}. \
+                 These numbers must be equal.", paths1.len(), paths2.len())))
+        }
+        paths1.iter().zip(paths2.iter())
+            .map(|(p1, p2)| JobInput::PairedFastq(p1.to_string(), p2.to_string()))
+            .collect()
+    } else if matches.is_present("interleaved") {
+        let arg = if matches.is_present("input-fastq") { "input-fastq" } else { "input" };
+        input_paths(arg).into_iter().map(JobInput::InterleavedFastq).collect()
+    } else if matches.is_present("input-fastq") {
+        input_paths("input-fastq").into_iter().map(JobInput::Fastq).collect()
+    } else if matches.is_present("input-fasta") {
+        input_paths("input-fasta").into_iter().map(JobInput::Fasta).collect()
     } else {
-        None
+        input_paths("input").into_iter().map(JobInput::Auto).collect()
     };
 
-    let sequence_name_prefix = match matches.is_present("sequence-prefix") {
-        true => Option::Some(matches.value_of("sequence-prefix").unwrap()),
-        false => Option::None
+    let mut job = ExtractionJob::builder()
+        .inputs(inputs)
+        .include_lists(&read_lists)
+        .exclude_lists(&exclude_lists)
+        .match_mode(MatchMode::from_name(matches.value_of("match-mode").unwrap())?)
+        .normaliser(NameNormaliser {
+            strip_after: matches.value_of("strip-after").map(|d| d.to_string()),
+            strip_mate_suffix: matches.is_present("strip-mate-suffix"),
+            case_fold: matches.is_present("ignore-case"),
+        })
+        .append(matches.is_present("append"))
+        .compress(!matches.is_present("output-uncompressed"))
+        .threads(parse_number(matches, "threads")?)
+        .on_missing(OnMissing::from_name(matches.value_of("on-missing").unwrap())?);
+    // --output-fastq-files and --output-fasta-files imply their format
+    let output_format = match matches.value_of("output-format") {
+        Some("fasta") => Some(SequenceFormat::Fasta),
+        Some(_) => Some(SequenceFormat::Fastq),
+        None if matches.is_present("output-fasta-files") => Some(SequenceFormat::Fasta),
+        None if matches.is_present("output-fastq-files") => Some(SequenceFormat::Fastq),
+        None => None,
     };
-
-    match doing_fastq {
-        true => {
-            if sequence_name_prefix.is_some() {
-                panic!("FASTQ output not current compatible with prefixes")
-            }
-            match output_compressed {
-                true => fastq_pipeline(input, name_index, compressed_outputs.unwrap()),
-                false => fastq_pipeline(input, name_index, uncompressed_outputs.unwrap()),
-            }
-        },
-        false => match output_compressed {
-            true => fasta_pipeline(input, name_index, compressed_outputs.unwrap(), sequence_name_prefix),
-            false => fasta_pipeline(input, name_index, uncompressed_outputs.unwrap(), sequence_name_prefix),
-        },
+    job = if let Some(template) = matches.value_of("output-template") {
+        job = job.output_template(template);
+        match matches.value_of("assignment-table") {
+            Some(table) => job.assignment_table(table),
+            None => job,
+        }
+    } else if matches.is_present("output-fastq-files-1") {
+        let output_files1: Vec<&str> = matches.values_of("output-fastq-files-1").unwrap().collect();
+        let output_files2: Vec<&str> = matches.values_of("output-fastq-files-2").unwrap().collect();
+        job.split_outputs(&output_files1, &output_files2)
+    } else {
+        let output_files: Vec<&str> = matches.values_of("output-files")
+            .or_else(|| matches.values_of("output-fasta-files"))
+            .or_else(|| matches.values_of("output-fastq-files"))
+            .unwrap()
+            .collect();
+        job.outputs(&output_files)
     };
-}
-
-fn open_a_file(path: &str, append: bool) -> File {
-    match append {
-        true => {
-            // must create the file, if it doesn't already exist
-            if !Path::new(path).exists() {
-                File::create(path).expect(&format!("Failed to create output file {}, before opening in append mode", path));
-            }
-            OpenOptions::new().append(true).open(path)
-                .expect(&format!("Failed to open output file {} for appending", path))
-        },
-        false => File::create(path)
-            .expect(&format!("Failed to open output file {} for writing", path)),
+    if let Some(format) = output_format {
+        job = job.output_format(format);
     }
-}
-
-struct NameIndex {
-    name_to_index: HashMap<String, HashSet<usize>>,
-    index_to_expected_count: Vec<usize>,
-}
-
-fn generate_name_index(read_lists: Vec<&str>) -> NameIndex {
-    // Read in each read name into has hashmap
-    let mut name_to_index: HashMap<String, HashSet<usize>> = HashMap::new();
-    let mut index_to_expected_count: Vec<usize> = vec![];
-    for (i, read_name_file) in read_lists.iter().enumerate() {
-        let mut lines_in_file: u64 = 0;
-        let reader1 = File::open(read_name_file)
-            .expect(&format!("Failed to open read name file {}", read_name_file));
-        let reader = BufReader::new(reader1);
-        for line in reader.lines() {
-            let name = line.unwrap();
-            // Ignore blank lines
-            if name != "" {
-                let insert = match name_to_index.get_mut(&name) {
-                    Some(prevs) => {
-                        if !prevs.insert(i) {
-                            panic!(
-                                "It appears that read '{}' was specified twice in input file {}",
-                                name, read_lists[i]);
-                        }
-                        false
-                    },
-                    None => true
-                };
-                if insert { // Do this to get around the borrow checker
-                    let mut set = HashSet::with_capacity(1);
-                    set.insert(i);
-                    name_to_index.insert(name.clone(), set);
-                }
-                lines_in_file += 1;
-            }
-        }
-        index_to_expected_count.push(lines_in_file as usize);
-        info!("Read in {} read names from {}", lines_in_file, read_name_file);
+    if let Some(compression) = matches.value_of("compression") {
+        job = job.compression(OutputCompression::from_name(compression)?);
     }
-
-    NameIndex {
-        name_to_index: name_to_index,
-        index_to_expected_count: index_to_expected_count
+    if matches.is_present("compression-level") {
+        job = job.compression_level(parse_number(matches, "compression-level")?);
     }
-}
-fn fastq_pipeline<W: Write>(
-    fastq_input: Option<BufReader<File>>,
-    name_index: NameIndex,
-    outputs: Vec<W>) {
-
-    match fastq_input {
-        Some(r) => read_fastq(
-            seq_io::fastq::Reader::new(r),
-            name_index.index_to_expected_count,
-            name_index.name_to_index,
-            outputs),
-        None => read_fastq(
-            seq_io::fastq::Reader::new(std::io::stdin()),
-            name_index.index_to_expected_count,
-            name_index.name_to_index,
-            outputs)
-    };
-}
-
-fn read_fastq<R, W>(
-    mut reader: seq_io::fastq::Reader<R>,
-    index_to_expected_count: Vec<usize>,
-    name_to_index: HashMap<String, HashSet<usize>>,
-    mut fastq_outputs: Vec<W>)
-where R: Read, W: Write {
-    info!("Iterating input FASTQ file");
-    let mut total_input_reads: usize = 0;
-    let mut index_to_observed_count: Vec<usize> = vec![0; index_to_expected_count.len()];
-
-    while let Some(record) = reader.next() {
-        let r2 = record.unwrap();
-        match name_to_index.get(r2.id()
-                                .expect("UTF8 error when decoding FASTQ header")) {
-            Some(indices) => {
-                for i in indices {
-                    index_to_observed_count[*i] += 1;
-                    r2.write(&mut fastq_outputs[*i]).expect("Failed to write a FASTQ record");
-                }
-            },
-            None => {}
-        };
-        total_input_reads += 1;
+    if let Some(prefix) = matches.value_of("sequence-prefix") {
+        job = job.sequence_prefix(prefix);
     }
-
-    let total_assigned_reads: usize = index_to_observed_count.iter().sum();
-    info!("Extracted {} reads from {} total", total_assigned_reads, total_input_reads);
-    if index_to_expected_count != index_to_observed_count {
-        panic!("Mismatching numbers of read names were observed. Expected:\n{:?}\nbut found\n{:?}",
-               index_to_expected_count, index_to_observed_count);
+    if let Some(suffix) = matches.value_of("sequence-suffix") {
+        job = job.sequence_suffix(suffix);
     }
-}
-
-fn fasta_pipeline<W: Write>(
-    input: Option<BufReader<File>>,
-    name_index: NameIndex,
-    outputs: Vec<W>,
-    sequence_name_prefix: Option<&str>) {
-
-
-    match input {
-        Some(r) => read_fasta(
-            seq_io::fasta::Reader::new(r),
-            name_index.index_to_expected_count,
-            name_index.name_to_index,
-            outputs,
-            sequence_name_prefix),
-        None => read_fasta(
-            seq_io::fasta::Reader::new(std::io::stdin()),
-            name_index.index_to_expected_count,
-            name_index.name_to_index,
-            outputs,
-            sequence_name_prefix)
-    };
-
-}
-
-
-fn read_fasta<R, W>( // TODO: This is duplicated code, but too lazy to fix right now.
-    mut reader: seq_io::fasta::Reader<R>,
-    index_to_expected_count: Vec<usize>,
-    name_to_index: HashMap<String, HashSet<usize>>,
-    mut fastq_outputs: Vec<W>,
-    sequence_name_prefix: Option<&str>)
-where R: Read, W: Write {
-    info!("Iterating input FASTQ file");
-    let mut total_input_reads: usize = 0;
-    let mut index_to_observed_count: Vec<usize> = vec![0; index_to_expected_count.len()];
-
-    while let Some(record) = reader.next() {
-        let r2 = record.unwrap();
-        match name_to_index.get(r2.id()
-                                .expect("UTF8 error when decoding FASTA header")) {
-            Some(indices) => {
-                for i in indices {
-                    index_to_observed_count[*i] += 1;
-                    match sequence_name_prefix {
-                        Some(pre) => {
-                            write!(&mut fastq_outputs[*i], ">{}{}\n{}\n",
-                                pre,
-                                r2.id().expect("UTF8 error when decoding FASTA header"),
-                                str::from_utf8(r2.seq())
-                                    .expect("Failed to convert FASTA sequence to UTF8"))
-                        },
-                        None => {
-                            r2.write(&mut fastq_outputs[*i])
-                        }
-                    }.expect("Failed to write to output file");
-                }
-            },
-            None => {}
+    job = job.drop_comments(matches.is_present("drop-comments"))
+        .keep_old_names(matches.is_present("keep-old-names"));
+    if matches.is_present("min-mean-quality") {
+        job = job.min_mean_quality(parse_number(matches, "min-mean-quality")?);
+    }
+    if let Some(path) = matches.value_of("unmatched-output") {
+        job = job.unmatched_output(path);
+    }
+    if let Some(report_path) = matches.value_of("report") {
+        let format = match matches.value_of("report-format") {
+            Some("json") => ReportFormat::Json,
+            Some("tsv") => ReportFormat::Tsv,
+            _ => ReportFormat::from_path(report_path),
         };
-        total_input_reads += 1;
+        job = job.report(report_path, format);
     }
-
-    let total_assigned_reads: usize = index_to_observed_count.iter().sum();
-    info!("Extracted {} reads from {} total", total_assigned_reads, total_input_reads);
-    if index_to_expected_count != index_to_observed_count {
-        panic!("Mismatching numbers of read names were observed. Expected:\n{:?}\nbut found\n{:?}",
-               index_to_expected_count, index_to_observed_count);
+    if let Some(dir) = matches.value_of("missing-names-dir") {
+        job = job.missing_names_dir(dir);
     }
+    job.build()?.run()
+}
+
+fn parse_number<T: std::str::FromStr>(matches: &ArgMatches, arg: &str) -> Result<T> {
+    let value = matches.value_of(arg).unwrap();
+    value.parse().map_err(|_| MfqeError::Argument(format!(
+        "--{} must be a number, found '{}'", arg, value)))
 }
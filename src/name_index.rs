@@ -0,0 +1,520 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::str;
+
+use regex::{RegexSet, RegexSetBuilder};
+
+use compact_index::{EntryLists, NameArena, Renames, MAX_ENTRIES, MAX_LISTS};
+use error::{MfqeError, Result};
+use input::decompressing_reader;
+use normalise::NameNormaliser;
+use paired::mate_common_name;
+use prefix_trie::PrefixTrie;
+
+/// How the entries of name lists are compared against sequence headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchMode {
+    /// The ID, i.e. the header up to the first space, equals the entry
+    Id,
+    /// The whole header line, including any comment, equals the entry
+    FullHeader,
+    /// The ID starts with the entry
+    Prefix,
+    /// The ID matches the entry as a regular expression
+    Regex,
+}
+
+impl MatchMode {
+    pub fn from_name(name: &str) -> Result<MatchMode> {
+        match name {
+            "id" => Ok(MatchMode::Id),
+            "full-header" => Ok(MatchMode::FullHeader),
+            "prefix" => Ok(MatchMode::Prefix),
+            "regex" => Ok(MatchMode::Regex),
+            _ => Err(MfqeError::Argument(format!("Unknown match mode '{}'", name))),
+        }
+    }
+}
+
+enum Matcher {
+    Id,
+    FullHeader,
+    Prefix(PrefixTrie),
+    // Pattern i of the set is entry i
+    Regex(RegexSet),
+}
+
+/// Whether an output receives the sequences named in its list, or every
+/// sequence except those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListPolarity {
+    Include,
+    Exclude,
+}
+
+/// The names of every list. Each distinct name is an entry, numbered from
+/// zero, which records the lists it came from, so that the entries matched
+/// by sequences can be tracked and those never matched reported.
+pub struct NameIndex {
+    names: NameArena,
+    entry_to_indices: EntryLists,
+    pub index_to_expected_count: Vec<usize>,
+    pub index_to_polarity: Vec<ListPolarity>,
+    exclusion_indices: Vec<usize>,
+    // The name sequences matching an entry are written with in the output of
+    // a list, from two-column lists
+    renames: Renames,
+    matcher: Matcher,
+    normaliser: NameNormaliser,
+}
+
+impl NameIndex {
+    /// Fill `entries` with the entries matching a sequence, given its ID and
+    /// full header line.
+    pub fn find(&self, id: &str, head: &[u8], entries: &mut Vec<usize>) -> Result<()> {
+        entries.clear();
+        self.find_more(id, head, entries)
+    }
+
+    /// Add the entries matching a sequence to `entries`, as for `find`.
+    pub fn find_more(&self, id: &str, head: &[u8], entries: &mut Vec<usize>) -> Result<()> {
+        let id = self.normaliser.normalise(id);
+        match self.matcher {
+            Matcher::Id => entries.extend(self.names.get(&id)),
+            Matcher::FullHeader => {
+                let head = str::from_utf8(head).map_err(|_| MfqeError::Utf8(format!(
+                    "UTF8 error when decoding sequence header of '{}'", id)))?;
+                entries.extend(self.names.get(&self.normaliser.normalise(head)))
+            },
+            Matcher::Prefix(ref trie) => trie.matching_entries(id.as_bytes(), entries),
+            Matcher::Regex(ref set) => entries.extend(set.matches(&id)),
+        }
+        Ok(())
+    }
+
+    pub fn num_entries(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The name of an entry.
+    pub fn name(&self, entry: usize) -> &str {
+        self.names.name(entry)
+    }
+
+    /// The indices of the lists an entry came from.
+    pub fn indices(&self, entry: usize) -> impl Iterator<Item=usize> + '_ {
+        self.entry_to_indices.iter(entry)
+    }
+
+    pub fn num_lists(&self) -> usize {
+        self.index_to_polarity.len()
+    }
+
+    /// Whether each list entry identifies exactly one sequence, so that the
+    /// number of sequences found can be checked against the list lengths.
+    /// Prefixes and regular expressions may match any number of sequences.
+    pub fn counts_are_exact(&self) -> bool {
+        match self.matcher {
+            Matcher::Id | Matcher::FullHeader => true,
+            Matcher::Prefix(_) | Matcher::Regex(..) => false,
+        }
+    }
+
+    /// Fill `destinations` with the indices of the outputs a sequence is
+    /// written to, given the entries it matched.
+    pub fn destinations(&self, entries: &[usize], destinations: &mut Vec<usize>) {
+        destinations.clear();
+        for e in entries {
+            destinations.extend(self.entry_to_indices.iter(*e)
+                                .filter(|i| self.index_to_polarity[*i] == ListPolarity::Include));
+        }
+        if entries.len() > 1 {
+            destinations.sort_unstable();
+            destinations.dedup();
+        }
+        for i in &self.exclusion_indices {
+            if !entries.iter().any(|e| self.entry_to_indices.contains(*e, *i)) {
+                destinations.push(*i);
+            }
+        }
+    }
+
+    /// The name a sequence matching `entries` is given when written to the
+    /// output of list `index`, if the list renames it.
+    pub fn new_name(&self, entries: &[usize], index: usize) -> Option<&str> {
+        if self.renames.is_empty() {
+            return None
+        }
+        entries.iter().find_map(|e| self.renames.get(*e, index))
+    }
+
+    /// The names of each list which were never matched, sorted, given
+    /// whether each entry was matched.
+    pub fn missing_names(&self, entry_observed: &[bool]) -> Vec<Vec<&str>> {
+        let mut missing: Vec<Vec<&str>> = vec![vec![]; self.num_lists()];
+        for (entry, observed) in entry_observed.iter().enumerate() {
+            if !observed {
+                for i in self.entry_to_indices.iter(entry) {
+                    missing[i].push(self.names.name(entry));
+                }
+            }
+        }
+        for names in missing.iter_mut() {
+            names.sort_unstable();
+        }
+        missing
+    }
+
+    /// Whether names are normalised before matching, so that distinct
+    /// sequences, such as both mates of a pair, may match the same name.
+    pub fn normalises_names(&self) -> bool {
+        !self.normaliser.is_identity()
+    }
+
+    /// The number of names of each list which were matched, given whether
+    /// each entry was matched.
+    pub fn found_name_counts(&self, entry_observed: &[bool]) -> Vec<usize> {
+        let mut counts = vec![0; self.num_lists()];
+        for (entry, observed) in entry_observed.iter().enumerate() {
+            if *observed {
+                for i in self.entry_to_indices.iter(entry) {
+                    counts[i] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// The number of sequences each output should receive. For an exclusion
+    /// list that is every input sequence except those in the list. For
+    /// paired input, a list naming both mates of a pair, or a pair and its
+    /// mates, expects that pair once.
+    pub fn expected_output_counts(&self, total_input: usize, paired: bool) -> Vec<usize> {
+        let mut index_to_expected_count = self.index_to_expected_count.clone();
+        if paired {
+            for (expected, redundant) in index_to_expected_count.iter_mut().zip(self.redundant_mate_counts()) {
+                *expected -= redundant;
+            }
+        }
+        index_to_expected_count.iter().zip(self.index_to_polarity.iter())
+            .map(|(expected, polarity)| match polarity {
+                ListPolarity::Include => *expected,
+                ListPolarity::Exclude => total_input.saturating_sub(*expected),
+            }).collect()
+    }
+
+    // The number of mate names in each list whose pair is already named in
+    // the list, by its common name or, for a second mate, by the name of its
+    // first mate.
+    fn redundant_mate_counts(&self) -> Vec<usize> {
+        let mut redundant = vec![0; self.num_lists()];
+        for entry in 0..self.num_entries() {
+            let name = self.names.name(entry);
+            let common = mate_common_name(name);
+            if common.len() == name.len() {
+                continue
+            }
+            let first_mate = match name.ends_with("/2") {
+                true => self.names.get(&format!("{}/1", common)),
+                false => None,
+            };
+            let named_pair = [self.names.get(common), first_mate];
+            for list in self.entry_to_indices.iter(entry) {
+                if named_pair.iter().flatten().any(|e| self.entry_to_indices.contains(*e, list)) {
+                    redundant[list] += 1;
+                }
+            }
+        }
+        redundant
+    }
+}
+
+/// Read each list of names into an index. Inclusion lists come first,
+/// followed by exclusion lists, so output i corresponds to the ith list in
+/// that order. Names are normalised as they are read, except for regular
+/// expressions, which are matched against normalised IDs as they are.
+///
+/// Lines of an inclusion list may instead have two tab-separated columns,
+/// the name and a new name for the sequences it matches. Lists may be
+/// compressed, and a list named "-" is read from STDIN.
+pub fn generate_name_index(
+    read_lists: &[&str],
+    exclude_lists: &[&str],
+    match_mode: MatchMode,
+    normaliser: NameNormaliser) -> Result<NameIndex> {
+
+    let mut builder = IndexBuilder::new(match_mode, normaliser);
+    let mut index_to_expected_count: Vec<usize> = vec![];
+    let mut index_to_polarity: Vec<ListPolarity> = vec![];
+    let all_lists = read_lists.iter().map(|l| (l, ListPolarity::Include))
+        .chain(exclude_lists.iter().map(|l| (l, ListPolarity::Exclude)));
+    for (i, (read_name_file, polarity)) in all_lists.enumerate() {
+        let mut lines_in_file: u64 = 0;
+        let mut collapsed_names: u64 = 0;
+        for line in read_lines(read_name_file)? {
+            let (line, new_name) = match split_rename(line?, read_name_file)? {
+                (_, Some(_)) if polarity == ListPolarity::Exclude => return Err(MfqeError::Argument(format!(
+                    "Exclusion list {} cannot rename sequences, but has two columns", read_name_file))),
+                split => split,
+            };
+            let name = builder.normalise(line);
+            // Ignore blank lines
+            if !name.is_empty() {
+                match builder.add(name, new_name, i, read_name_file)? {
+                    true => lines_in_file += 1,
+                    false => collapsed_names += 1,
+                }
+            }
+        }
+        warn_collapsed(collapsed_names, read_name_file);
+        index_to_expected_count.push(lines_in_file as usize);
+        index_to_polarity.push(polarity);
+        match polarity {
+            ListPolarity::Include => info!("Read in {} read names from {}", lines_in_file, read_name_file),
+            ListPolarity::Exclude => info!("Read in {} read names to exclude from {}", lines_in_file, read_name_file),
+        }
+    }
+    builder.finish(index_to_expected_count, index_to_polarity)
+}
+
+/// Read a table of sequence names and labels, separated by a tab, into an
+/// index with one inclusion list per distinct label, numbered in the order
+/// the labels first appear. Returns the index and the labels.
+pub fn generate_name_index_from_table(
+    table: &str,
+    match_mode: MatchMode,
+    normaliser: NameNormaliser) -> Result<(NameIndex, Vec<String>)> {
+
+    let mut builder = IndexBuilder::new(match_mode, normaliser);
+    let mut label_to_index: HashMap<String, usize> = HashMap::new();
+    let mut labels: Vec<String> = vec![];
+    let mut index_to_expected_count: Vec<usize> = vec![];
+    let mut collapsed_names: u64 = 0;
+    for line in read_lines(table)? {
+        let line = line?;
+        // Ignore blank lines
+        if line.is_empty() {
+            continue
+        }
+        let (name, label) = match line.split_once('\t') {
+            Some((name, label)) if !name.is_empty() && !label.is_empty() && !label.contains('\t') => (name, label),
+            _ => return Err(MfqeError::Parse(format!(
+                "Invalid line '{}' in assignment table {}: expected a sequence name, \
+                 a tab and a label", line, table))),
+        };
+        let i = match label_to_index.get(label) {
+            Some(i) => *i,
+            None => {
+                label_to_index.insert(label.to_string(), labels.len());
+                labels.push(label.to_string());
+                index_to_expected_count.push(0);
+                labels.len() - 1
+            },
+        };
+        let name = builder.normalise(name.to_string());
+        if !name.is_empty() {
+            match builder.add(name, None, i, table)? {
+                true => index_to_expected_count[i] += 1,
+                false => collapsed_names += 1,
+            }
+        }
+    }
+    warn_collapsed(collapsed_names, table);
+    info!("Read in {} read names with {} labels from {}",
+          index_to_expected_count.iter().sum::<usize>(), labels.len(), table);
+    let index_to_polarity = vec![ListPolarity::Include; labels.len()];
+    Ok((builder.finish(index_to_expected_count, index_to_polarity)?, labels))
+}
+
+// The lines of a file of names, or of STDIN if the path is "-", which may be
+// compressed, failing with a UTF-8 error on invalid text.
+fn read_lines<'a>(path: &'a str) -> Result<impl Iterator<Item=Result<String>> + 'a> {
+    let reader = match path {
+        "-" => decompressing_reader(io::stdin()),
+        _ => {
+            let file = File::open(path).map_err(
+                |e| MfqeError::io(format!("Failed to open read name file {}", path), e))?;
+            decompressing_reader(BufReader::new(file))
+        },
+    }.map_err(|e| MfqeError::io(format!("Failed to read read name file {}", path), e))?;
+    Ok(BufReader::new(reader).lines().map(move |line| line.map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => MfqeError::Utf8(format!(
+            "UTF8 error when reading read name file {}", path)),
+        _ => MfqeError::io(format!("Failed to read read name file {}", path), e),
+    })))
+}
+
+fn warn_collapsed(collapsed_names: u64, path: &str) {
+    if collapsed_names > 0 {
+        warn!("{} names in {} were the same as an earlier name once normalised, \
+               and were ignored", collapsed_names, path);
+    }
+}
+
+// The most memory a compiled RegexSet of the names may use, and its lazy
+// DFA cache may use per thread, which are the regex crate's defaults
+const REGEX_SIZE_LIMIT: usize = 10 * (1 << 20);
+const REGEX_DFA_SIZE_LIMIT: usize = 2 * (1 << 20);
+
+// Numbers the distinct names of lists as entries, as they are read.
+struct IndexBuilder {
+    names: NameArena,
+    entry_to_indices: EntryLists,
+    renames: Renames,
+    match_mode: MatchMode,
+    normaliser: NameNormaliser,
+}
+
+impl IndexBuilder {
+    fn new(match_mode: MatchMode, normaliser: NameNormaliser) -> IndexBuilder {
+        IndexBuilder {
+            names: NameArena::new(),
+            entry_to_indices: EntryLists::new(),
+            renames: Renames::new(),
+            match_mode,
+            normaliser,
+        }
+    }
+
+    fn normalise(&self, name: String) -> String {
+        match self.match_mode {
+            MatchMode::Regex => name,
+            _ => self.normaliser.normalise(&name).into_owned(),
+        }
+    }
+
+    // Add a name of list i, returning false if it was ignored because it was
+    // the same as an earlier name of the list once normalised.
+    fn add(&mut self, name: String, new_name: Option<String>, i: usize, list: &str) -> Result<bool> {
+        if i >= MAX_LISTS {
+            return Err(MfqeError::Argument(format!("At most {} lists can be given", MAX_LISTS)))
+        }
+        if self.names.len() >= MAX_ENTRIES {
+            return Err(MfqeError::Argument(format!("At most {} distinct names can be given", MAX_ENTRIES)))
+        }
+        let entry = match self.names.insert(&name) {
+            (entry, true) => {
+                self.entry_to_indices.push(i);
+                entry
+            },
+            (entry, false) => {
+                if !self.entry_to_indices.insert(entry, i) {
+                    // Distinct names in the list, such as both mates of a
+                    // pair, may normalise to the same name.
+                    if self.normaliser.is_identity() {
+                        return Err(MfqeError::DuplicateName {
+                            name,
+                            list: list.to_string(),
+                        });
+                    }
+                    return Ok(false)
+                }
+                entry
+            },
+        };
+        if let Some(new_name) = new_name {
+            if self.renames.num_new_names() >= MAX_ENTRIES {
+                return Err(MfqeError::Argument(format!("At most {} distinct new names can be given", MAX_ENTRIES)))
+            }
+            self.renames.insert(entry, i, &new_name);
+        }
+        Ok(true)
+    }
+
+    fn finish(
+        self,
+        index_to_expected_count: Vec<usize>,
+        index_to_polarity: Vec<ListPolarity>) -> Result<NameIndex> {
+
+        let names = self.names;
+        let entry_to_indices = self.entry_to_indices;
+        let mut renames = self.renames;
+        renames.finish();
+        let exclusion_indices = index_to_polarity.iter().enumerate()
+            .filter(|(_, p)| **p == ListPolarity::Exclude)
+            .map(|(i, _)| i)
+            .collect();
+        let matcher = match self.match_mode {
+            MatchMode::Id => Matcher::Id,
+            MatchMode::FullHeader => Matcher::FullHeader,
+            MatchMode::Prefix => {
+                let mut trie = PrefixTrie::new();
+                for entry in 0..names.len() {
+                    trie.insert(names.name(entry).as_bytes(), entry);
+                }
+                Matcher::Prefix(trie)
+            },
+            MatchMode::Regex => {
+                let patterns = (0..names.len()).map(|entry| names.name(entry));
+                let set = RegexSetBuilder::new(patterns)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .dfa_size_limit(REGEX_DFA_SIZE_LIMIT)
+                    .case_insensitive(self.normaliser.case_fold)
+                    .build()
+                    .map_err(|e| MfqeError::Parse(format!("Failed to parse regular expression: {}", e)))?;
+                Matcher::Regex(set)
+            },
+        };
+        // The size of a compiled RegexSet is not exposed, so only its limits
+        // are logged
+        let matcher_bytes = match matcher {
+            Matcher::Prefix(ref trie) => trie.heap_bytes(),
+            _ => 0,
+        };
+        // The tables briefly took more memory as they grew, while the other
+        // structures were no bigger than they are now
+        let max_bytes = names.heap_bytes() + entry_to_indices.heap_bytes() + renames.heap_bytes() + matcher_bytes
+            + names.growth_bytes().max(renames.growth_bytes());
+        info!("The index of {} distinct names used at most {} of memory",
+              names.len(), format_bytes(max_bytes));
+        if let Matcher::Regex(_) = matcher {
+            info!("The regular expressions are limited to {} of memory once compiled, and {} per thread while matching",
+                  format_bytes(REGEX_SIZE_LIMIT), format_bytes(REGEX_DFA_SIZE_LIMIT));
+        }
+        Ok(NameIndex {
+            names,
+            entry_to_indices,
+            index_to_expected_count,
+            index_to_polarity,
+            exclusion_indices,
+            renames,
+            matcher,
+            normaliser: self.normaliser,
+        })
+    }
+}
+
+// A number of bytes in the most suitable unit, e.g. 1.5 GiB.
+fn format_bytes(bytes: usize) -> String {
+    let units = ["bytes", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} bytes", bytes),
+        _ => format!("{:.1} {}", size, units[unit]),
+    }
+}
+
+// Split a line of a list into the name and, if there is a second column, the
+// new name.
+fn split_rename(mut line: String, read_name_file: &str) -> Result<(String, Option<String>)> {
+    let tab = match line.find('\t') {
+        Some(tab) => tab,
+        None => return Ok((line, None)),
+    };
+    let new_name = line[tab+1..].to_string();
+    line.truncate(tab);
+    if new_name.is_empty() || new_name.contains(char::is_whitespace) {
+        return Err(MfqeError::Parse(format!(
+            "Invalid new name '{}' for '{}' in {}: expected two tab-separated columns, \
+             the second a name without whitespace", new_name, line, read_name_file)))
+    }
+    Ok((line, Some(new_name)))
+}
@@ -0,0 +1,38 @@
+use std::borrow::Cow;
+
+use paired::mate_common_name;
+
+/// Rewrites names before they are compared, applied both to the entries of
+/// name lists and to the IDs of sequences, so that naming conventions which
+/// differ between the two can still be matched.
+#[derive(Debug, Clone, Default)]
+pub struct NameNormaliser {
+    /// Remove everything from the first occurrence of this delimiter onwards
+    pub strip_after: Option<String>,
+    /// Remove a trailing /1 or /2
+    pub strip_mate_suffix: bool,
+    /// Compare names case-insensitively
+    pub case_fold: bool,
+}
+
+impl NameNormaliser {
+    pub fn is_identity(&self) -> bool {
+        self.strip_after.is_none() && !self.strip_mate_suffix && !self.case_fold
+    }
+
+    pub fn normalise<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        let mut normalised = name;
+        if let Some(ref delimiter) = self.strip_after {
+            if let Some(pos) = normalised.find(delimiter.as_str()) {
+                normalised = &normalised[..pos];
+            }
+        }
+        if self.strip_mate_suffix {
+            normalised = mate_common_name(normalised);
+        }
+        match self.case_fold {
+            true => Cow::Owned(normalised.to_lowercase()),
+            false => Cow::Borrowed(normalised),
+        }
+    }
+}
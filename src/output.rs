@@ -0,0 +1,255 @@
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use xz2::stream::MtStreamBuilder;
+use xz2::write::XzEncoder;
+use zstd::stream::write::Encoder as ZstdEncoder;
+
+use bgzf_index::BgzfIndexer;
+use error::{MfqeError, Result};
+use input::SequenceFormat;
+use parallel_gz::ParallelGzEncoder;
+use record::{HeaderEdit, SeqRecord};
+
+/// A destination for sequences, in a particular format.
+pub struct SequenceOutput<W: Write> {
+    pub writer: W,
+    pub format: SequenceFormat,
+}
+
+impl<W: Write> SequenceOutput<W> {
+    pub fn new(writer: W, format: SequenceFormat) -> SequenceOutput<W> {
+        SequenceOutput { writer, format }
+    }
+
+    /// Write a record in the format of this output, with its ID replaced by
+    /// `new_id` if given, and its header changed by `edit`.
+    pub fn write<R: SeqRecord>(&mut self, record: &R, edit: &HeaderEdit, new_id: Option<&str>) -> Result<()> {
+        record.write_as(&mut self.writer, self.format, edit, new_id)
+    }
+}
+
+/// Compression formats outputs can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputCompression {
+    Gzip,
+    /// The blocked gzip of bgzip, readable as gzip
+    Bgzf,
+    Zstd,
+    Xz,
+    Uncompressed,
+}
+
+impl OutputCompression {
+    pub fn from_name(name: &str) -> Result<OutputCompression> {
+        match name {
+            "gzip" => Ok(OutputCompression::Gzip),
+            "bgzf" => Ok(OutputCompression::Bgzf),
+            "zstd" => Ok(OutputCompression::Zstd),
+            "xz" => Ok(OutputCompression::Xz),
+            "none" => Ok(OutputCompression::Uncompressed),
+            _ => Err(MfqeError::Argument(format!("Unknown compression '{}'", name))),
+        }
+    }
+
+    /// The compression implied by the extension of a path, e.g. zstd for
+    /// reads.fq.zst, or none for reads.fq. Paths with neither a compression
+    /// nor a sequence extension imply nothing.
+    pub fn from_path(path: &str) -> Option<OutputCompression> {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".gz") {
+            Some(OutputCompression::Gzip)
+        } else if lower.ends_with(".bgz") {
+            Some(OutputCompression::Bgzf)
+        } else if lower.ends_with(".zst") {
+            Some(OutputCompression::Zstd)
+        } else if lower.ends_with(".xz") {
+            Some(OutputCompression::Xz)
+        } else if SequenceFormat::from_path(path).is_some() {
+            Some(OutputCompression::Uncompressed)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            OutputCompression::Gzip => "gzip",
+            OutputCompression::Bgzf => "bgzf",
+            OutputCompression::Zstd => "zstd",
+            OutputCompression::Xz => "xz",
+            OutputCompression::Uncompressed => "none",
+        }
+    }
+
+    // The levels accepted by the compressor, if it has any, and the level
+    // used when none is given
+    fn levels(&self) -> Option<(u32, u32, u32)> {
+        match *self {
+            OutputCompression::Gzip | OutputCompression::Bgzf | OutputCompression::Xz => Some((0, 9, 6)),
+            OutputCompression::Zstd => Some((1, 22, 3)),
+            OutputCompression::Uncompressed => None,
+        }
+    }
+
+    /// The level to compress at, which is `level` if given and acceptable,
+    /// otherwise the default level of the compressor. Levels are ignored
+    /// when writing uncompressed.
+    pub fn level(&self, level: Option<u32>) -> Result<u32> {
+        match (self.levels(), level) {
+            (Some((_, _, default)), None) => Ok(default),
+            (Some((min, max, _)), Some(l)) if l >= min && l <= max => Ok(l),
+            (Some((min, max, _)), Some(l)) => Err(MfqeError::Argument(format!(
+                "The {} compression level must be between {} and {}, but was {}",
+                self.name(), min, max, l))),
+            (None, _) => Ok(0),
+        }
+    }
+}
+
+/// How output files are opened and compressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputOptions {
+    /// Append to existing files rather than overwriting them
+    pub append: bool,
+    /// The compression of every output, or None to choose it from the
+    /// extension of each, falling back to gzip
+    pub compression: Option<OutputCompression>,
+    /// The compression level, or None for the compressor's default
+    pub level: Option<u32>,
+    /// Threads used to compress each output, in parallel blocks if more
+    /// than one
+    pub threads: usize,
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions {
+            append: false,
+            compression: None,
+            level: None,
+            threads: 1,
+        }
+    }
+}
+
+impl OutputOptions {
+    /// The compression an output at `path` is written with.
+    pub fn compression_of(&self, path: &str) -> OutputCompression {
+        self.compression
+            .or_else(|| OutputCompression::from_path(path))
+            .unwrap_or(OutputCompression::Gzip)
+    }
+}
+
+/// A writer of an output file, which is finished once everything has been
+/// written, completing its compression and writing any index, so that any
+/// error doing so is not lost as it would be when it is dropped.
+pub trait OutputWriter: Write {
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl OutputWriter for BufWriter<File> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl OutputWriter for GzEncoder<BufWriter<File>> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl OutputWriter for ParallelGzEncoder<BufWriter<File>> {
+    fn finish(&mut self) -> io::Result<()> {
+        ParallelGzEncoder::finish(self)
+    }
+}
+
+impl OutputWriter for ZstdEncoder<'static, BufWriter<File>> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.do_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+impl OutputWriter for XzEncoder<BufWriter<File>> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().flush()
+    }
+}
+
+// Open an output file of sequences in the given format, compressed as given
+// by the options or its extension, creating its directory if needed. BGZF
+// outputs are indexed as they are written.
+pub fn open_output(path: &str, format: SequenceFormat, options: &OutputOptions) -> Result<Box<dyn OutputWriter>> {
+    let compression = options.compression_of(path);
+    let level = compression.level(options.level)?;
+    if compression == OutputCompression::Bgzf && options.append {
+        return Err(MfqeError::Argument(format!(
+            "Cannot append to BGZF output {}, since its index would not cover what is already there",
+            path)))
+    }
+    debug!("Writing {} with {} compression at level {}", path, compression.name(), level);
+    if let Some(dir) = Path::new(path).parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            fs::create_dir_all(dir).map_err(|e| MfqeError::io(format!(
+                "Failed to create the directory of output file {}", path), e))?;
+        }
+    }
+    let w1 = BufWriter::new(open_a_file(path, options.append)?);
+    let threads = options.threads;
+    Ok(match (compression, threads) {
+        (OutputCompression::Gzip, 1) => Box::new(GzEncoder::new(w1, Compression::new(level))),
+        (OutputCompression::Gzip, _) => Box::new(ParallelGzEncoder::new(w1, threads, Compression::new(level))),
+        (OutputCompression::Bgzf, _) => {
+            let index = BgzfIndexer::new(path, format == SequenceFormat::Fasta);
+            Box::new(ParallelGzEncoder::bgzf(w1, threads, Compression::new(level), Some(index)))
+        },
+        (OutputCompression::Zstd, _) => {
+            let mut encoder = ZstdEncoder::new(w1, level as i32)
+                .map_err(|e| MfqeError::io(format!("Failed to start zstd compression of {}", path), e))?;
+            if threads > 1 {
+                encoder.multithread(threads as u32)
+                    .map_err(|e| MfqeError::io(format!("Failed to use threads for zstd compression of {}", path), e))?;
+            }
+            Box::new(encoder)
+        },
+        (OutputCompression::Xz, 1) => Box::new(XzEncoder::new(w1, level)),
+        (OutputCompression::Xz, _) => {
+            let stream = MtStreamBuilder::new()
+                .threads(threads as u32)
+                .preset(level)
+                .encoder()
+                .map_err(|e| MfqeError::io(format!("Failed to start xz compression of {}", path), e.into()))?;
+            Box::new(XzEncoder::new_stream(w1, stream))
+        },
+        (OutputCompression::Uncompressed, _) => Box::new(w1),
+    })
+}
+
+fn open_a_file(path: &str, append: bool) -> Result<File> {
+    match append {
+        true => {
+            // must create the file, if it doesn't already exist
+            if !Path::new(path).exists() {
+                File::create(path).map_err(|e| MfqeError::io(format!(
+                    "Failed to create output file {}, before opening in append mode", path), e))?;
+            }
+            OpenOptions::new().append(true).open(path)
+                .map_err(|e| MfqeError::io(format!("Failed to open output file {} for appending", path), e))
+        },
+        false => File::create(path)
+            .map_err(|e| MfqeError::io(format!("Failed to open output file {} for writing", path), e)),
+    }
+}
@@ -0,0 +1,215 @@
+use std::io::prelude::*;
+
+use seq_io::fastq::Reader;
+
+use error::{MfqeError, Result};
+use name_index::NameIndex;
+use output::SequenceOutput;
+use pipeline::RecordOptions;
+use record::SeqRecord;
+use report::ExtractionSummary;
+
+/// The name shared by both mates of a pair, i.e. the read ID with any
+/// trailing /1 or /2 removed.
+pub fn mate_common_name(id: &str) -> &str {
+    if id.ends_with("/1") || id.ends_with("/2") {
+        &id[..id.len()-2]
+    } else {
+        id
+    }
+}
+
+/// Fill `entries` with the name index entries a pair matches: those of its
+/// common name, so lists of names without mate suffixes work, and those of
+/// the full ID of each mate, so a pair named by either or both of its mates
+/// is found, in the same list or in different ones.
+pub fn pair_entries<R1: SeqRecord, R2: SeqRecord>(
+    name_index: &NameIndex,
+    record1: &R1,
+    record2: &R2,
+    entries: &mut Vec<usize>) -> Result<()> {
+
+    let id1 = record1.id()?;
+    let id2 = record2.id()?;
+    let common_name = mate_common_name(id1);
+    name_index.find(common_name, record1.head(), entries)?;
+    for (id, head) in [(id1, record1.head()), (id2, record2.head())] {
+        if id != common_name {
+            name_index.find_more(id, head, entries)?;
+        }
+    }
+    if entries.len() > 1 {
+        entries.sort_unstable();
+        entries.dedup();
+    }
+    Ok(())
+}
+
+/// The ID a mate is written with when its pair is renamed to `new_name`,
+/// keeping any trailing /1 or /2.
+pub fn mate_new_id(id: &str, new_name: &str) -> String {
+    format!("{}{}", new_name, &id[mate_common_name(id).len()..])
+}
+
+/// Fail unless the two mates of a pair have the same common name.
+pub fn check_mates(id1: &str, id2: &str) -> Result<()> {
+    match mate_common_name(id1) == mate_common_name(id2) {
+        true => Ok(()),
+        false => Err(MfqeError::Parse(format!(
+            "Mismatching mate names found: '{}' and '{}' are not from the same pair", id1, id2))),
+    }
+}
+
+pub fn paired_fastq_pipeline<W: Write>(
+    input1: Box<dyn Read>,
+    input2: Box<dyn Read>,
+    name_index: &NameIndex,
+    outputs1: &mut [SequenceOutput<W>],
+    outputs2: &mut [SequenceOutput<W>],
+    options: &RecordOptions) -> Result<ExtractionSummary> {
+
+    read_paired_fastq(
+        Reader::new(input1),
+        Reader::new(input2),
+        name_index,
+        outputs1,
+        outputs2,
+        options)
+}
+
+fn read_paired_fastq<R, W>(
+    mut reader1: Reader<R>,
+    mut reader2: Reader<R>,
+    name_index: &NameIndex,
+    outputs1: &mut [SequenceOutput<W>],
+    outputs2: &mut [SequenceOutput<W>],
+    options: &RecordOptions) -> Result<ExtractionSummary>
+where R: Read, W: Write {
+    info!("Iterating paired input FASTQ files");
+    let mut extractor = PairExtractor::new(name_index, outputs1, Some(outputs2), options);
+
+    loop {
+        let (record1, record2) = match (reader1.next(), reader2.next()) {
+            (Some(r1), Some(r2)) => (r1?, r2?),
+            (None, None) => break,
+            _ => return Err(MfqeError::Parse(
+                "The R1 and R2 FASTQ inputs contain different numbers of reads".to_string()))
+        };
+        extractor.extract_pair(&record1, &record2)?;
+    }
+
+    Ok(extractor.finish())
+}
+
+/// Extract pairs from an interleaved FASTQ, where each record is immediately
+/// followed by its mate. Pairs are written interleaved to `outputs1`, unless
+/// `outputs2` is given, in which case the second mate of each pair is written
+/// there instead.
+pub fn interleaved_fastq_pipeline<W: Write>(
+    input: Box<dyn Read>,
+    name_index: &NameIndex,
+    outputs1: &mut [SequenceOutput<W>],
+    outputs2: Option<&mut [SequenceOutput<W>]>,
+    options: &RecordOptions) -> Result<ExtractionSummary> {
+
+    read_interleaved_fastq(
+        Reader::new(input),
+        name_index,
+        outputs1,
+        outputs2,
+        options)
+}
+
+fn read_interleaved_fastq<R, W>(
+    mut reader: Reader<R>,
+    name_index: &NameIndex,
+    outputs1: &mut [SequenceOutput<W>],
+    outputs2: Option<&mut [SequenceOutput<W>]>,
+    options: &RecordOptions) -> Result<ExtractionSummary>
+where R: Read, W: Write {
+    info!("Iterating interleaved input FASTQ file");
+    let mut extractor = PairExtractor::new(name_index, outputs1, outputs2, options);
+
+    while let Some(r) = reader.next() {
+        // The first mate must be copied out of the reader's buffer before
+        // the second can be read.
+        let record1 = r?.to_owned_record();
+        let record2 = match reader.next() {
+            Some(r) => r?,
+            None => return Err(MfqeError::Parse(format!(
+                "Interleaved FASTQ input contains an odd number of reads, \
+                 the last read '{}' has no mate", record1.id()?)))
+        };
+        extractor.extract_pair(&record1, &record2)?;
+    }
+
+    Ok(extractor.finish())
+}
+
+/// Writes each pair of paired input to the outputs of the lists it belongs
+/// to, as `pipeline::Extractor` does for single-end input. The second mate is
+/// written to `outputs2` if given, and otherwise after the first in
+/// `outputs1`.
+struct PairExtractor<'a, W: Write> {
+    name_index: &'a NameIndex,
+    outputs1: &'a mut [SequenceOutput<W>],
+    outputs2: Option<&'a mut [SequenceOutput<W>]>,
+    options: &'a RecordOptions,
+    summary: ExtractionSummary,
+    entries: Vec<usize>,
+    destinations: Vec<usize>,
+}
+
+impl<'a, W: Write> PairExtractor<'a, W> {
+    fn new(
+        name_index: &'a NameIndex,
+        outputs1: &'a mut [SequenceOutput<W>],
+        outputs2: Option<&'a mut [SequenceOutput<W>]>,
+        options: &'a RecordOptions) -> PairExtractor<'a, W> {
+
+        PairExtractor {
+            name_index,
+            summary: ExtractionSummary::new(outputs1.len(), name_index.num_entries()),
+            outputs1,
+            outputs2,
+            options,
+            entries: vec![],
+            destinations: vec![],
+        }
+    }
+
+    fn extract_pair<R1: SeqRecord, R2: SeqRecord>(&mut self, record1: &R1, record2: &R2) -> Result<()> {
+        let (id1, id2) = (record1.id()?, record2.id()?);
+        check_mates(id1, id2)?;
+
+        pair_entries(self.name_index, record1, record2, &mut self.entries)?;
+        self.summary.observe(&self.entries);
+        self.name_index.destinations(&self.entries, &mut self.destinations);
+        let bases = record1.num_bases() + record2.num_bases();
+        self.summary.add_input(bases);
+        // A pair is only written if both mates pass
+        if !self.options.passes_filters(record1) || !self.options.passes_filters(record2) {
+            self.summary.add_filtered(&self.destinations);
+            return Ok(())
+        }
+        let edit = &self.options.header_edit;
+        for i in &self.destinations {
+            self.summary.add_output(*i, bases);
+            let new_ids = self.name_index.new_name(&self.entries, *i)
+                .map(|n| (mate_new_id(id1, n), mate_new_id(id2, n)));
+            let new_id1 = new_ids.as_ref().map(|n| n.0.as_str());
+            let new_id2 = new_ids.as_ref().map(|n| n.1.as_str());
+            self.outputs1[*i].write(record1, edit, new_id1)?;
+            match self.outputs2 {
+                Some(ref mut o2) => o2[*i].write(record2, edit, new_id2)?,
+                None => self.outputs1[*i].write(record2, edit, new_id2)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> ExtractionSummary {
+        info!("Extracted {} read pairs from {} total", self.summary.total_assigned(), self.summary.total_input);
+        self.summary
+    }
+}
@@ -0,0 +1,198 @@
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::thread;
+
+use flate2::{Compression, Crc};
+use flate2::write::{DeflateEncoder, GzEncoder};
+
+use bgzf_index::BgzfIndexer;
+
+// The amount of uncompressed data in each gzip member, as in pigz
+pub const BLOCK_SIZE: usize = 128 * 1024;
+
+// The amount of uncompressed data in each BGZF block, as in bgzip, which
+// keeps each compressed block under the 64 KiB limit of the format
+pub const BGZF_BLOCK_SIZE: usize = 0xff00;
+
+// The gzip header of a BGZF block, up to the 2 byte total block size less
+// one, which ends its extra field
+const BGZF_HEADER: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0];
+
+// The empty block which marks the end of a BGZF file
+const BGZF_EOF: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x04, 0, 0, 0, 0, 0, 0xff, 0x06, 0, b'B', b'C', 0x02, 0,
+    0x1b, 0, 0x03, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockFormat {
+    Gzip,
+    Bgzf,
+}
+
+/// A gzip writer which compresses blocks of its input on several threads at
+/// once. Each block is written as a separate gzip member, so the output is a
+/// valid multi-member gzip file which decompresses to exactly the input, and
+/// is the same whatever the number of threads.
+pub struct ParallelGzEncoder<W: Write> {
+    inner: W,
+    threads: usize,
+    level: Compression,
+    format: BlockFormat,
+    block_size: usize,
+    index: Option<BgzfIndexer>,
+    // Full blocks waiting to be compressed, and the block being filled
+    blocks: Vec<Vec<u8>>,
+    current: Vec<u8>,
+    members_written: usize,
+    finished: bool,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    pub fn new(inner: W, threads: usize, level: Compression) -> ParallelGzEncoder<W> {
+        ParallelGzEncoder::with_format(inner, threads, level, BlockFormat::Gzip, BLOCK_SIZE)
+    }
+
+    /// A writer of BGZF, the blocked gzip of bgzip and samtools, which ends
+    /// with the BGZF end of file marker. If an indexer is given, the index
+    /// files are written once the output is finished.
+    pub fn bgzf(
+        inner: W,
+        threads: usize,
+        level: Compression,
+        index: Option<BgzfIndexer>) -> ParallelGzEncoder<W> {
+
+        let mut encoder = ParallelGzEncoder::with_format(
+            inner, threads, level, BlockFormat::Bgzf, BGZF_BLOCK_SIZE);
+        encoder.index = index;
+        encoder
+    }
+
+    fn with_format(
+        inner: W,
+        threads: usize,
+        level: Compression,
+        format: BlockFormat,
+        block_size: usize) -> ParallelGzEncoder<W> {
+
+        ParallelGzEncoder {
+            inner,
+            threads: threads.max(1),
+            level,
+            format,
+            block_size,
+            index: None,
+            blocks: Vec::with_capacity(threads),
+            current: Vec::with_capacity(block_size),
+            members_written: 0,
+            finished: false,
+        }
+    }
+
+    /// Compress and write any remaining input. An empty gzip input is
+    /// written as a single empty gzip member, so that the output is still
+    /// valid gzip.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(())
+        }
+        if !self.current.is_empty()
+            || (self.members_written == 0 && self.format == BlockFormat::Gzip) {
+            self.blocks.push(mem::take(&mut self.current));
+        }
+        self.write_blocks()?;
+        if self.format == BlockFormat::Bgzf {
+            self.inner.write_all(BGZF_EOF)?;
+        }
+        self.finished = true;
+        self.inner.flush()?;
+        match self.index {
+            Some(ref mut index) => index.write_files(),
+            None => Ok(()),
+        }
+    }
+
+    // Compress the waiting blocks, one per thread, and write them in order.
+    // A single block, e.g. every block with one thread, is compressed on
+    // this thread rather than one spawned for it.
+    fn write_blocks(&mut self) -> io::Result<()> {
+        let (level, format) = (self.level, self.format);
+        let compress = move |block: &[u8]| match format {
+            BlockFormat::Gzip => compress_block(block, level),
+            BlockFormat::Bgzf => compress_bgzf_block(block, level),
+        };
+        let members: Vec<io::Result<Vec<u8>>> = match self.blocks.len() {
+            0 | 1 => self.blocks.iter().map(|block| compress(block)).collect(),
+            _ => thread::scope(|scope| {
+                let handles: Vec<_> = self.blocks.iter()
+                    .map(|block| scope.spawn(move || compress(block)))
+                    .collect();
+                handles.into_iter()
+                    .map(|h| h.join().unwrap_or_else(|_| Err(io::Error::other("A compression thread panicked"))))
+                    .collect()
+            }),
+        };
+        for (block, member) in self.blocks.iter().zip(members) {
+            let member = member?;
+            self.inner.write_all(&member)?;
+            self.members_written += 1;
+            if let Some(ref mut index) = self.index {
+                index.add_block(block, member.len());
+            }
+        }
+        self.blocks.clear();
+        Ok(())
+    }
+}
+
+fn compress_block(block: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(block.len() / 2), level);
+    encoder.write_all(block)?;
+    encoder.finish()
+}
+
+fn compress_bgzf_block(block: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut member = Vec::with_capacity(block.len() / 2);
+    member.extend_from_slice(BGZF_HEADER);
+    // The block size is filled in once the block is compressed
+    member.extend_from_slice(&[0, 0]);
+    let mut encoder = DeflateEncoder::new(member, level);
+    encoder.write_all(block)?;
+    let mut member = encoder.finish()?;
+    let mut crc = Crc::new();
+    crc.update(block);
+    member.extend_from_slice(&crc.sum().to_le_bytes());
+    member.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    let size = (member.len() - 1) as u16;
+    member[BGZF_HEADER.len()..BGZF_HEADER.len() + 2].copy_from_slice(&size.to_le_bytes());
+    Ok(member)
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let taken = buf.len().min(self.block_size - self.current.len());
+        self.current.extend_from_slice(&buf[..taken]);
+        if self.current.len() == self.block_size {
+            let block = mem::replace(&mut self.current, Vec::with_capacity(self.block_size));
+            self.blocks.push(block);
+            if self.blocks.len() == self.threads {
+                self.write_blocks()?;
+            }
+        }
+        Ok(taken)
+    }
+
+    // Blocks are only compressed once full, so that the output does not
+    // depend on when the writer is flushed.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ParallelGzEncoder<W> {
+    fn drop(&mut self) {
+        // Errors cannot be returned from drop, as with GzEncoder
+        let _ = self.finish();
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::prelude::*;
+
+use seq_io::fasta;
+use seq_io::fastq;
+
+use error::Result;
+use name_index::NameIndex;
+use output::SequenceOutput;
+use record::{HeaderEdit, SeqRecord};
+use report::ExtractionSummary;
+
+/// Changes made to, and filters applied to, records as they are extracted.
+#[derive(Debug, Clone, Default)]
+pub struct RecordOptions {
+    /// Changes made to the header of each record written to a list's output
+    pub header_edit: HeaderEdit,
+    /// Records with a lower mean quality are not written. Only for FASTQ.
+    pub min_mean_quality: Option<f64>,
+}
+
+impl RecordOptions {
+    pub fn passes_filters<R: SeqRecord>(&self, record: &R) -> bool {
+        match (self.min_mean_quality, record.mean_quality()) {
+            (Some(min), Some(quality)) => quality >= min,
+            _ => true,
+        }
+    }
+}
+
+pub fn fastq_pipeline<W: Write>(
+    input: Box<dyn Read>,
+    name_index: &NameIndex,
+    outputs: &mut [SequenceOutput<W>],
+    unmatched_output: Option<&mut SequenceOutput<W>>,
+    options: &RecordOptions) -> Result<ExtractionSummary> {
+
+    info!("Iterating input FASTQ file");
+    let mut reader = fastq::Reader::new(input);
+    let mut extractor = Extractor::new(name_index, outputs, unmatched_output, options);
+    while let Some(record) = reader.next() {
+        extractor.extract(&record?)?;
+    }
+    Ok(extractor.finish())
+}
+
+pub fn fasta_pipeline<W: Write>(
+    input: Box<dyn Read>,
+    name_index: &NameIndex,
+    outputs: &mut [SequenceOutput<W>],
+    unmatched_output: Option<&mut SequenceOutput<W>>,
+    options: &RecordOptions) -> Result<ExtractionSummary> {
+
+    info!("Iterating input FASTA file");
+    let mut reader = fasta::Reader::new(input);
+    let mut extractor = Extractor::new(name_index, outputs, unmatched_output, options);
+    while let Some(record) = reader.next() {
+        extractor.extract(&record?)?;
+    }
+    Ok(extractor.finish())
+}
+
+/// Writes each record of single-end input to the outputs of the lists it
+/// belongs to, whatever its format, keeping count as it goes.
+pub struct Extractor<'a, W: Write> {
+    name_index: &'a NameIndex,
+    outputs: &'a mut [SequenceOutput<W>],
+    unmatched_output: Option<&'a mut SequenceOutput<W>>,
+    options: &'a RecordOptions,
+    summary: ExtractionSummary,
+    entries: Vec<usize>,
+    destinations: Vec<usize>,
+}
+
+impl<'a, W: Write> Extractor<'a, W> {
+    pub fn new(
+        name_index: &'a NameIndex,
+        outputs: &'a mut [SequenceOutput<W>],
+        unmatched_output: Option<&'a mut SequenceOutput<W>>,
+        options: &'a RecordOptions) -> Extractor<'a, W> {
+
+        Extractor {
+            name_index,
+            summary: ExtractionSummary::new(outputs.len(), name_index.num_entries()),
+            outputs,
+            unmatched_output,
+            options,
+            entries: vec![],
+            destinations: vec![],
+        }
+    }
+
+    pub fn extract<R: SeqRecord>(&mut self, record: &R) -> Result<()> {
+        let bases = record.num_bases();
+        self.name_index.find(record.id()?, record.head(), &mut self.entries)?;
+        self.summary.observe(&self.entries);
+        self.name_index.destinations(&self.entries, &mut self.destinations);
+        self.summary.add_input(bases);
+        if !self.options.passes_filters(record) {
+            self.summary.add_filtered(&self.destinations);
+            return Ok(())
+        }
+        for i in &self.destinations {
+            self.summary.add_output(*i, bases);
+            let new_id = self.name_index.new_name(&self.entries, *i);
+            self.outputs[*i].write(record, &self.options.header_edit, new_id)?;
+        }
+        if self.entries.is_empty() {
+            if let Some(ref mut unmatched) = self.unmatched_output {
+                unmatched.write(record, &HeaderEdit::default(), None)?;
+                self.summary.add_unmatched(bases);
+            }
+        }
+        Ok(())
+    }
+
+    /// Finish extraction, returning the counts. Outputs are left open, so
+    /// that further inputs can be extracted to them.
+    pub fn finish(self) -> ExtractionSummary {
+        info!("Extracted {} reads from {} total", self.summary.total_assigned(), self.summary.total_input);
+        if self.unmatched_output.is_some() {
+            info!("Wrote {} reads matching none of the name lists", self.summary.total_unmatched);
+        }
+        if self.options.min_mean_quality.is_some() {
+            info!("Filtered out {} reads below the minimum mean quality", self.summary.total_filtered);
+        }
+        self.summary
+    }
+}
@@ -0,0 +1,54 @@
+/// A byte-wise trie of name prefixes, each recording its entry in the name
+/// index, so that all prefixes of a name can be found in a single walk down
+/// the trie rather than by checking every prefix in turn.
+pub struct PrefixTrie {
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Default)]
+struct TrieNode {
+    // Sorted by byte, for binary search
+    children: Vec<(u8, usize)>,
+    entry: Option<usize>,
+}
+
+impl PrefixTrie {
+    pub fn new() -> PrefixTrie {
+        PrefixTrie { nodes: vec![TrieNode::default()] }
+    }
+
+    pub fn insert(&mut self, prefix: &[u8], entry: usize) {
+        let mut node = 0;
+        for b in prefix {
+            node = match self.nodes[node].children.binary_search_by_key(b, |c| c.0) {
+                Ok(pos) => self.nodes[node].children[pos].1,
+                Err(pos) => {
+                    let child = self.nodes.len();
+                    self.nodes.push(TrieNode::default());
+                    self.nodes[node].children.insert(pos, (*b, child));
+                    child
+                }
+            };
+        }
+        self.nodes[node].entry = Some(entry);
+    }
+
+    /// The memory used by the trie.
+    pub fn heap_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<TrieNode>()
+            + self.nodes.iter().map(|n| n.children.capacity() * std::mem::size_of::<(u8, usize)>()).sum::<usize>()
+    }
+
+    /// Add the entries of every stored prefix of `name` to `entries`.
+    pub fn matching_entries(&self, name: &[u8], entries: &mut Vec<usize>) {
+        entries.extend(self.nodes[0].entry);
+        let mut node = 0;
+        for b in name {
+            match self.nodes[node].children.binary_search_by_key(b, |c| c.0) {
+                Ok(pos) => node = self.nodes[node].children[pos].1,
+                Err(_) => break,
+            }
+            entries.extend(self.nodes[node].entry);
+        }
+    }
+}
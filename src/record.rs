@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::io;
+use std::io::prelude::*;
+
+use seq_io::fasta;
+use seq_io::fasta::Record as FastaRecord;
+use seq_io::fastq;
+use seq_io::fastq::Record as FastqRecord;
+
+use error::{utf8_id, MfqeError, Result};
+use input::SequenceFormat;
+
+/// Changes made to the header of a record as it is written.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderEdit {
+    /// Added to the start of the ID
+    pub prefix: Option<String>,
+    /// Added to the end of the ID
+    pub suffix: Option<String>,
+    /// Write only the ID, dropping the comment (description) after it
+    pub drop_comment: bool,
+    /// When a sequence is renamed, put its original ID at the start of the
+    /// comment
+    pub keep_old_name: bool,
+}
+
+impl HeaderEdit {
+    /// True if headers are written unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.prefix.is_none() && self.suffix.is_none() && !self.drop_comment
+    }
+}
+
+/// A FASTQ or FASTA record, so that sequences of either format can be
+/// extracted by the same code.
+pub trait SeqRecord {
+    /// The header line, without the leading '@' or '>'
+    fn head(&self) -> &[u8];
+
+    /// The ID, i.e. the header up to the first space
+    fn id(&self) -> Result<&str>;
+
+    /// The rest of the header after the first space, if any
+    fn desc(&self) -> Option<&[u8]>;
+
+    /// The sequence, without any line breaks
+    fn seq(&self) -> Cow<'_, [u8]>;
+
+    /// Quality scores, which only FASTQ records have
+    fn qual(&self) -> Option<&[u8]>;
+
+    fn num_bases(&self) -> usize {
+        self.seq().len()
+    }
+
+    /// Write the record in its own format.
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    /// Write the record in the given format, with its ID replaced by
+    /// `new_id` if given, and its header changed by `edit`. FASTQ records are
+    /// written as FASTA by dropping their qualities, but FASTA records cannot
+    /// be written as FASTQ.
+    fn write_as<W: Write>(
+        &self,
+        writer: &mut W,
+        format: SequenceFormat,
+        edit: &HeaderEdit,
+        new_id: Option<&str>) -> Result<()> {
+
+        if edit.is_identity() && new_id.is_none() && self.format() == format {
+            return self.write(writer)
+                .map_err(|e| MfqeError::io("Failed to write a sequence record", e));
+        }
+        let qual = match (format, self.qual()) {
+            (SequenceFormat::Fasta, _) => None,
+            (SequenceFormat::Fastq, Some(qual)) => Some(qual),
+            (SequenceFormat::Fastq, None) => return Err(MfqeError::Argument(
+                "FASTA records cannot be written as FASTQ".to_string())),
+        };
+        let id = self.id()?;
+        let marker: &[u8] = match format {
+            SequenceFormat::Fastq => b"@",
+            SequenceFormat::Fasta => b">",
+        };
+        writer.write_all(marker)
+            .and_then(|_| write!(writer, "{}{}{}",
+                                 edit.prefix.as_deref().unwrap_or(""),
+                                 new_id.unwrap_or(id),
+                                 edit.suffix.as_deref().unwrap_or("")))
+            .and_then(|_| match (new_id, edit.keep_old_name) {
+                (Some(_), true) => write!(writer, " {}", id),
+                _ => Ok(()),
+            })
+            .and_then(|_| match (self.desc(), edit.drop_comment) {
+                (Some(desc), false) => writer.write_all(b" ").and_then(|_| writer.write_all(desc)),
+                _ => Ok(()),
+            })
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| writer.write_all(&self.seq()))
+            .and_then(|_| writer.write_all(b"\n"))
+            .and_then(|_| match qual {
+                Some(qual) => writer.write_all(b"+\n")
+                    .and_then(|_| writer.write_all(qual))
+                    .and_then(|_| writer.write_all(b"\n")),
+                None => Ok(()),
+            })
+            .map_err(|e| MfqeError::io("Failed to write a sequence record", e))
+    }
+
+    fn format(&self) -> SequenceFormat {
+        match self.qual() {
+            Some(_) => SequenceFormat::Fastq,
+            None => SequenceFormat::Fasta,
+        }
+    }
+
+    /// The mean Phred quality score, assuming Phred+33 encoding, or None for
+    /// FASTA records.
+    fn mean_quality(&self) -> Option<f64> {
+        self.qual().map(|qual| match qual.is_empty() {
+            true => 0.0,
+            false => qual.iter().map(|q| (q.saturating_sub(33)) as f64).sum::<f64>() / qual.len() as f64,
+        })
+    }
+}
+
+impl<'a> SeqRecord for fastq::RefRecord<'a> {
+    fn head(&self) -> &[u8] { FastqRecord::head(self) }
+    fn id(&self) -> Result<&str> { utf8_id(FastqRecord::id(self), "FASTQ") }
+    fn desc(&self) -> Option<&[u8]> { FastqRecord::desc_bytes(self) }
+    fn seq(&self) -> Cow<'_, [u8]> { Cow::Borrowed(FastqRecord::seq(self)) }
+    fn qual(&self) -> Option<&[u8]> { Some(FastqRecord::qual(self)) }
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> { FastqRecord::write(self, writer) }
+}
+
+impl SeqRecord for fastq::OwnedRecord {
+    fn head(&self) -> &[u8] { FastqRecord::head(self) }
+    fn id(&self) -> Result<&str> { utf8_id(FastqRecord::id(self), "FASTQ") }
+    fn desc(&self) -> Option<&[u8]> { FastqRecord::desc_bytes(self) }
+    fn seq(&self) -> Cow<'_, [u8]> { Cow::Borrowed(FastqRecord::seq(self)) }
+    fn qual(&self) -> Option<&[u8]> { Some(FastqRecord::qual(self)) }
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> { FastqRecord::write(self, writer) }
+}
+
+impl<'a> SeqRecord for fasta::RefRecord<'a> {
+    fn head(&self) -> &[u8] { FastaRecord::head(self) }
+    fn id(&self) -> Result<&str> { utf8_id(FastaRecord::id(self), "FASTA") }
+    fn desc(&self) -> Option<&[u8]> { FastaRecord::desc_bytes(self) }
+    fn seq(&self) -> Cow<'_, [u8]> { self.full_seq() }
+    fn qual(&self) -> Option<&[u8]> { None }
+    fn num_bases(&self) -> usize { self.seq_lines().map(|l| l.len()).sum() }
+    fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> { FastaRecord::write(self, writer) }
+}
@@ -0,0 +1,406 @@
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use serde_json;
+
+use error::{MfqeError, Result};
+use name_index::{ListPolarity, NameIndex};
+
+/// What was read and written during a run. For paired input each pair
+/// counts as a single record, and its bases are those of both mates.
+pub struct ExtractionSummary {
+    pub total_input: usize,
+    pub total_input_bases: usize,
+    pub index_to_observed_count: Vec<usize>,
+    pub index_to_observed_bases: Vec<usize>,
+    pub total_unmatched: usize,
+    pub total_unmatched_bases: usize,
+    /// Records which would have been written to each output, but failed a
+    /// filter such as --min-mean-quality
+    pub index_to_filtered_count: Vec<usize>,
+    /// Records which failed a filter, whether or not they matched a list
+    pub total_filtered: usize,
+    /// Whether each entry of the name index matched any sequence
+    pub entry_observed: Vec<bool>,
+    /// What was read from each input and written from it, in the order the
+    /// inputs were read
+    pub inputs: Vec<InputSummary>,
+}
+
+/// The records read from one input, and written from it to each output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSummary {
+    /// The path of the input, both paths separated by a comma for paired
+    /// input, or - for STDIN
+    pub input: String,
+    pub records: usize,
+    pub bases: usize,
+    pub index_to_observed_count: Vec<usize>,
+}
+
+impl ExtractionSummary {
+    pub fn new(num_outputs: usize, num_entries: usize) -> ExtractionSummary {
+        ExtractionSummary {
+            total_input: 0,
+            total_input_bases: 0,
+            index_to_observed_count: vec![0; num_outputs],
+            index_to_observed_bases: vec![0; num_outputs],
+            total_unmatched: 0,
+            total_unmatched_bases: 0,
+            index_to_filtered_count: vec![0; num_outputs],
+            total_filtered: 0,
+            entry_observed: vec![false; num_entries],
+            inputs: vec![],
+        }
+    }
+
+    /// Add the counts of a single input to those of the run.
+    pub fn merge(&mut self, input: &str, other: ExtractionSummary) {
+        self.total_input += other.total_input;
+        self.total_input_bases += other.total_input_bases;
+        add_counts(&mut self.index_to_observed_count, &other.index_to_observed_count);
+        add_counts(&mut self.index_to_observed_bases, &other.index_to_observed_bases);
+        self.total_unmatched += other.total_unmatched;
+        self.total_unmatched_bases += other.total_unmatched_bases;
+        add_counts(&mut self.index_to_filtered_count, &other.index_to_filtered_count);
+        self.total_filtered += other.total_filtered;
+        for (observed, other_observed) in self.entry_observed.iter_mut().zip(other.entry_observed) {
+            *observed |= other_observed;
+        }
+        self.inputs.push(InputSummary {
+            input: input.to_string(),
+            records: other.total_input,
+            bases: other.total_input_bases,
+            index_to_observed_count: other.index_to_observed_count,
+        });
+    }
+
+    pub fn add_input(&mut self, bases: usize) {
+        self.total_input += 1;
+        self.total_input_bases += bases;
+    }
+
+    pub fn observe(&mut self, entries: &[usize]) {
+        for e in entries {
+            self.entry_observed[*e] = true;
+        }
+    }
+
+    pub fn add_output(&mut self, index: usize, bases: usize) {
+        self.index_to_observed_count[index] += 1;
+        self.index_to_observed_bases[index] += bases;
+    }
+
+    pub fn add_unmatched(&mut self, bases: usize) {
+        self.total_unmatched += 1;
+        self.total_unmatched_bases += bases;
+    }
+
+    /// Count a record which failed a filter, given the outputs it would
+    /// otherwise have been written to.
+    pub fn add_filtered(&mut self, destinations: &[usize]) {
+        for i in destinations {
+            self.index_to_filtered_count[*i] += 1;
+        }
+        self.total_filtered += 1;
+    }
+
+    pub fn total_assigned(&self) -> usize {
+        self.index_to_observed_count.iter().sum()
+    }
+}
+
+fn add_counts(counts: &mut [usize], other: &[usize]) {
+    for (count, other_count) in counts.iter_mut().zip(other) {
+        *count += other_count;
+    }
+}
+
+// The most missing names of each list given when the counts do not match
+const MAX_MISSING_NAMES_SHOWN: usize = 10;
+
+/// What to do when the number of sequences written to an output differs
+/// from the number expected from its name list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnMissing {
+    /// Fail with MfqeError::CountMismatch
+    Error,
+    /// Log the mismatch as a warning and carry on
+    Warn,
+    /// Carry on silently
+    Ignore,
+}
+
+impl OnMissing {
+    pub fn from_name(name: &str) -> Result<OnMissing> {
+        match name {
+            "error" => Ok(OnMissing::Error),
+            "warn" => Ok(OnMissing::Warn),
+            "ignore" => Ok(OnMissing::Ignore),
+            _ => Err(MfqeError::Argument(format!("Unknown --on-missing policy '{}'", name))),
+        }
+    }
+}
+
+/// A description of how the number of sequences written to each output
+/// differs from the number expected from the name lists, including some of
+/// the names of each list which were not found, or None if they agree.
+/// `paired` is whether the input was read as pairs.
+pub fn count_mismatch(
+    lists: &[&str],
+    name_index: &NameIndex,
+    summary: &ExtractionSummary,
+    paired: bool) -> Option<String> {
+
+    if !name_index.counts_are_exact() {
+        return None;
+    }
+    let (index_to_expected_count, index_to_found_count) = match name_index.normalises_names() {
+        // Distinct sequences may match the same normalised name, so only
+        // whether each name was found can be checked
+        true => (name_index.index_to_expected_count.clone(),
+                 name_index.found_name_counts(&summary.entry_observed)),
+        // Filtered records were found, even though they were not written
+        false => (name_index.expected_output_counts(summary.total_input, paired),
+                  summary.index_to_observed_count.iter()
+                      .zip(summary.index_to_filtered_count.iter())
+                      .map(|(observed, filtered)| observed + filtered)
+                      .collect()),
+    };
+    if index_to_expected_count == index_to_found_count {
+        return None;
+    }
+    let missing_names = name_index.missing_names(&summary.entry_observed);
+    let mut details = String::new();
+    for (list, names) in lists.iter().zip(missing_names.iter()) {
+        if names.is_empty() {
+            continue;
+        }
+        details.push_str(&format!("\n{} name(s) from {} were not found: {}",
+                                  names.len(), list, truncated_names(names)));
+    }
+    if details.is_empty() {
+        details.push_str("\nEvery name was found, so some sequences must occur \
+                          more than once in the input");
+    }
+    Some(format!("Mismatching numbers of read names were observed. Expected:\n{:?}\nbut found\n{:?}{}",
+                 index_to_expected_count, index_to_found_count, details))
+}
+
+fn truncated_names(names: &[&str]) -> String {
+    let shown = names.iter().take(MAX_MISSING_NAMES_SHOWN)
+        .map(|n| n.to_string()).collect::<Vec<_>>().join(", ");
+    match names.len() > MAX_MISSING_NAMES_SHOWN {
+        true => format!("{}, ... ({} more)", shown, names.len() - MAX_MISSING_NAMES_SHOWN),
+        false => shown,
+    }
+}
+
+/// Write the names of each list which matched no sequence to a file in
+/// `dir` named after the list, with the extension .missing.txt, one name per
+/// line. The directory is created if needed.
+pub fn write_missing_names(
+    dir: &str,
+    lists: &[&str],
+    name_index: &NameIndex,
+    summary: &ExtractionSummary) -> Result<()> {
+
+    fs::create_dir_all(dir).map_err(
+        |e| MfqeError::io(format!("Failed to create missing names directory {}", dir), e))?;
+    let missing_names = name_index.missing_names(&summary.entry_observed);
+    let mut written: Vec<String> = vec![];
+    for (list, names) in lists.iter().zip(missing_names.iter()) {
+        let file_name = match Path::new(list).file_name() {
+            Some(f) => format!("{}.missing.txt", f.to_string_lossy()),
+            None => return Err(MfqeError::Argument(format!(
+                "Failed to determine the file name of list {}", list))),
+        };
+        if written.contains(&file_name) {
+            return Err(MfqeError::Argument(format!(
+                "More than one list is named {}, so their missing names cannot be \
+                 written to separate files", file_name)));
+        }
+        let path = Path::new(dir).join(&file_name);
+        let mut writer = BufWriter::new(File::create(&path).map_err(|e| MfqeError::io(
+            format!("Failed to open missing names file {} for writing", path.display()), e))?);
+        names.iter().try_for_each(|name| writeln!(writer, "{}", name))
+            .and_then(|_| writer.flush())
+            .map_err(|e| MfqeError::io(
+                format!("Failed to write missing names file {}", path.display()), e))?;
+        if !names.is_empty() {
+            info!("{} name(s) from {} were not found", names.len(), list);
+        }
+        written.push(file_name);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Tsv,
+}
+
+impl ReportFormat {
+    /// TSV if the path ends in .tsv, otherwise JSON.
+    pub fn from_path(path: &str) -> ReportFormat {
+        match path.ends_with(".tsv") {
+            true => ReportFormat::Tsv,
+            false => ReportFormat::Json,
+        }
+    }
+}
+
+struct ListReport<'a> {
+    list: &'a str,
+    outputs: &'a [&'a str],
+    polarity: ListPolarity,
+    expected_names: usize,
+    records: usize,
+    bases: usize,
+    missing: usize,
+    filtered: usize,
+}
+
+fn list_reports<'a>(
+    lists: &'a [&'a str],
+    index_to_outputs: &'a [Vec<&'a str>],
+    name_index: &NameIndex,
+    summary: &ExtractionSummary) -> Vec<ListReport<'a>> {
+
+    let missing_names = name_index.missing_names(&summary.entry_observed);
+    lists.iter().enumerate().map(|(i, list)| {
+        ListReport {
+            list,
+            outputs: &index_to_outputs[i],
+            polarity: name_index.index_to_polarity[i],
+            expected_names: name_index.index_to_expected_count[i],
+            records: summary.index_to_observed_count[i],
+            bases: summary.index_to_observed_bases[i],
+            missing: missing_names[i].len(),
+            filtered: summary.index_to_filtered_count[i],
+        }
+    }).collect()
+}
+
+fn polarity_name(polarity: ListPolarity) -> &'static str {
+    match polarity {
+        ListPolarity::Include => "include",
+        ListPolarity::Exclude => "exclude",
+    }
+}
+
+/// Write a report of expected and observed counts for each list, along with
+/// totals for the run. `index_to_outputs` gives the output path(s) of each
+/// list, and `lists` the path of each list, in name index order.
+pub fn write_report(
+    path: &str,
+    format: ReportFormat,
+    lists: &[&str],
+    index_to_outputs: &[Vec<&str>],
+    unmatched_output: Option<&str>,
+    name_index: &NameIndex,
+    summary: &ExtractionSummary) -> Result<()> {
+
+    let reports = list_reports(lists, index_to_outputs, name_index, summary);
+    let mut writer = BufWriter::new(File::create(path).map_err(
+        |e| MfqeError::io(format!("Failed to open report file {} for writing", path), e))?);
+    match format {
+        ReportFormat::Json => write_json(&mut writer, &reports, unmatched_output, summary),
+        ReportFormat::Tsv => write_tsv(&mut writer, &reports, unmatched_output, summary),
+    }.and_then(|_| writer.flush())
+        .map_err(|e| MfqeError::io(format!("Failed to write report file {}", path), e))?;
+    info!("Wrote report to {}", path);
+    Ok(())
+}
+
+fn write_json<W: Write>(
+    writer: &mut W,
+    reports: &[ListReport],
+    unmatched_output: Option<&str>,
+    summary: &ExtractionSummary) -> std::io::Result<()> {
+
+    let lists: Vec<serde_json::Value> = reports.iter().map(|r| json!({
+        "list": r.list,
+        "outputs": r.outputs,
+        "polarity": polarity_name(r.polarity),
+        "expected_names": r.expected_names,
+        "records": r.records,
+        "bases": r.bases,
+        "missing": r.missing,
+        "filtered": r.filtered,
+    })).collect();
+    let unmatched = unmatched_output.map(|o| json!({
+        "output": o,
+        "records": summary.total_unmatched,
+        "bases": summary.total_unmatched_bases,
+    }));
+    let inputs: Vec<serde_json::Value> = summary.inputs.iter().map(|i| json!({
+        "input": i.input,
+        "records": i.records,
+        "bases": i.bases,
+        "records_written": i.index_to_observed_count,
+    })).collect();
+    let missing: usize = reports.iter().map(|r| r.missing).sum();
+    let report = json!({
+        "lists": lists,
+        "unmatched": unmatched,
+        "inputs": inputs,
+        "totals": {
+            "input_records": summary.total_input,
+            "input_bases": summary.total_input_bases,
+            "records_written": summary.total_assigned(),
+            "bases_written": summary.index_to_observed_bases.iter().sum::<usize>(),
+            "missing": missing,
+            "filtered": summary.total_filtered,
+        },
+    });
+    serde_json::to_writer_pretty(&mut *writer, &report)?;
+    writeln!(writer)
+}
+
+fn write_tsv<W: Write>(
+    writer: &mut W,
+    reports: &[ListReport],
+    unmatched_output: Option<&str>,
+    summary: &ExtractionSummary) -> std::io::Result<()> {
+
+    writeln!(writer, "list\toutputs\tpolarity\texpected_names\trecords\tbases\tmissing\tfiltered\tinput")?;
+    for r in reports {
+        writeln!(writer, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\tNA",
+                 r.list, r.outputs.join(","), polarity_name(r.polarity),
+                 r.expected_names, r.records, r.bases, r.missing, r.filtered)?;
+    }
+    // When there are several inputs, the records each wrote to each list's
+    // outputs are given in rows of their own, with the input in the input
+    // column, which is NA for rows covering every input.
+    if summary.inputs.len() > 1 {
+        for i in &summary.inputs {
+            for (r, records) in reports.iter().zip(&i.index_to_observed_count) {
+                writeln!(writer, "{}\t{}\t{}\tNA\t{}\tNA\tNA\tNA\t{}",
+                         r.list, r.outputs.join(","), polarity_name(r.polarity), records, i.input)?;
+            }
+        }
+    }
+    // Rows for the run as a whole are marked with asterisks, which are
+    // unlikely to start the path of a list.
+    if let Some(o) = unmatched_output {
+        writeln!(writer, "*unmatched*\t{}\tNA\tNA\t{}\t{}\tNA\tNA\tNA",
+                 o, summary.total_unmatched, summary.total_unmatched_bases)?;
+    }
+    if summary.inputs.len() > 1 {
+        for i in &summary.inputs {
+            writeln!(writer, "*input*\tNA\tNA\tNA\t{}\t{}\tNA\tNA\t{}", i.records, i.bases, i.input)?;
+        }
+    }
+    writeln!(writer, "*input*\tNA\tNA\tNA\t{}\t{}\tNA\t{}\tNA",
+             summary.total_input, summary.total_input_bases, summary.total_filtered)?;
+    writeln!(writer, "*total*\tNA\tNA\t{}\t{}\t{}\t{}\t{}\tNA",
+             reports.iter().map(|r| r.expected_names).sum::<usize>(),
+             summary.total_assigned(),
+             summary.index_to_observed_bases.iter().sum::<usize>(),
+             reports.iter().map(|r| r.missing).sum::<usize>(),
+             reports.iter().map(|r| r.filtered).sum::<usize>())
+}
@@ -1,5 +1,9 @@
+// Some tests write fixture lines with write! and an explicit newline
+#![allow(clippy::write_with_newline)]
+
 extern crate assert_cli;
 extern crate tempfile;
+extern crate serde_json;
 
 #[cfg(test)]
 mod tests {
@@ -264,4 +268,1874 @@ mod tests {
             .stdout().is(">i am a prefixrandom_sequence_length_5_1\n\
                           GGTGT\n").unwrap();
     }
+
+    #[test]
+    fn test_fastq_compressed_inputs(){
+        for input in &["tests/data/1.fq.gz", "tests/data/1.fq.bz2",
+                       "tests/data/1.fq.xz", "tests/data/1.fq.zst"] {
+            let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+            let t = tf.path().to_str().unwrap();
+            Assert::main_binary()
+                .with_args(&[
+                    "--fastq-read-name-lists",
+                    "tests/data/input1",
+                    "--output-fastq-files",
+                    t,
+                    "--input-fastq",
+                    input]).succeeds().unwrap();
+            Assert::command(&["zcat",t])
+                .stdout().is("@random_sequence_length_5_1 1\n\
+                              TAGGG\n\
+                              +\n\
+                              AAAAA\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_fastq_gzip_by_stdin(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let mut contents = vec![];
+        std::fs::File::open("tests/data/1.fq.gz").unwrap().read_to_end(&mut contents).unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input2",
+                "--output-fastq-files",
+                t])
+            .stdin(contents)
+            .succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @random_sequence_length_5_2 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n").unwrap();
+    }
+
+    #[test]
+    fn test_fasta_multi_member_gzip(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fasta-read-name-lists",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta.gz"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_2\n\
+                          TTATG\n").unwrap();
+    }
+
+    #[test]
+    fn test_paired_fastq(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                t1,
+                "--output-fastq-files-2",
+                t2]).succeeds().unwrap();
+        Assert::command(&["zcat",t1])
+            .stdout().is("@random_sequence_length_5_1/1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @random_sequence_length_5_2/1\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n").unwrap();
+        Assert::command(&["zcat",t2])
+            .stdout().is("@random_sequence_length_5_1/2\n\
+                          CCCTA\n\
+                          +\n\
+                          AAAAB\n\
+                          @random_sequence_length_5_2/2\n\
+                          TGAAA\n\
+                          +\n\
+                          ACGTA\n").unwrap();
+    }
+
+    #[test]
+    fn test_paired_fastq_both_mates_listed(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_both_mates",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                t1,
+                "--output-fastq-files-2",
+                t2]).succeeds().unwrap();
+        Assert::command(&["zcat",t1])
+            .stdout().is("@random_sequence_length_5_1/1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+        Assert::command(&["zcat",t2])
+            .stdout().is("@random_sequence_length_5_1/2\n\
+                          CCCTA\n\
+                          +\n\
+                          AAAAB\n").unwrap();
+    }
+
+    #[test]
+    fn test_paired_fastq_mates_in_different_lists(){
+        let td = tempfile::TempDir::new().unwrap();
+        let list1 = td.path().join("mate1");
+        let list2 = td.path().join("mate2");
+        std::fs::write(&list1, "random_sequence_length_5_1/1\n").unwrap();
+        std::fs::write(&list2, "random_sequence_length_5_1/2\n").unwrap();
+        let outputs: Vec<String> = ["a_1.fq", "b_1.fq", "a_2.fq", "b_2.fq"].iter()
+            .map(|o| td.path().join(o).to_str().unwrap().to_string())
+            .collect();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                list1.to_str().unwrap(),
+                list2.to_str().unwrap(),
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                &outputs[0],
+                &outputs[1],
+                "--output-fastq-files-2",
+                &outputs[2],
+                &outputs[3]]).succeeds().unwrap();
+        // The pair goes to the outputs of both lists
+        for output in &outputs[..2] {
+            assert_eq!("@random_sequence_length_5_1/1\nTAGGG\n+\nAAAAA\n",
+                       std::fs::read_to_string(output).unwrap());
+        }
+        for output in &outputs[2..] {
+            assert_eq!("@random_sequence_length_5_1/2\nCCCTA\n+\nAAAAB\n",
+                       std::fs::read_to_string(output).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_paired_fastq_mismatched_mates(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2_mismatched.fq",
+                "--output-fastq-files-1",
+                t1,
+                "--output-fastq-files-2",
+                t2])
+            .fails_with(5)
+            .stderr().contains("Mismatching mate names")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_interleaved_fastq_to_interleaved(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_mate2",
+                "--interleaved",
+                "--input-fastq",
+                "tests/data/interleaved.fq",
+                "--output-fastq-files",
+                t]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_2/1\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n\
+                          @random_sequence_length_5_2/2\n\
+                          TGAAA\n\
+                          +\n\
+                          ACGTA\n").unwrap();
+    }
+
+    #[test]
+    fn test_interleaved_fastq_both_mates_listed(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_both_mates",
+                "--interleaved",
+                "--input-fastq",
+                "tests/data/interleaved.fq",
+                "--output-fastq-files",
+                t]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1/1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @random_sequence_length_5_1/2\n\
+                          CCCTA\n\
+                          +\n\
+                          AAAAB\n").unwrap();
+    }
+
+    #[test]
+    fn test_interleaved_fastq_to_split(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--interleaved",
+                "--input-fastq",
+                "tests/data/interleaved.fq",
+                "--output-fastq-files-1",
+                t1,
+                "--output-fastq-files-2",
+                t2]).succeeds().unwrap();
+        Assert::command(&["zcat",t1])
+            .stdout().is("@random_sequence_length_5_1/1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+        Assert::command(&["zcat",t2])
+            .stdout().is("@random_sequence_length_5_1/2\n\
+                          CCCTA\n\
+                          +\n\
+                          AAAAB\n").unwrap();
+    }
+
+    #[test]
+    fn test_interleaved_fastq_odd_number_of_reads(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--interleaved",
+                "--input-fastq",
+                "tests/data/interleaved_odd.fq",
+                "--output-fastq-files",
+                t])
+            .fails_with(5)
+            .stderr().contains("odd number of reads")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fastq_unmatched_output(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf_unmatched: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t_unmatched = tf_unmatched.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--unmatched-output",
+                t_unmatched,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+        Assert::command(&["zcat",t_unmatched])
+            .stdout().is("@random_sequence_length_5_2 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n\
+                          @random_sequence_length_5_3 4\n\
+                          TCTAC\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_fasta_unmatched_output(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        let tf_unmatched: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t_unmatched = tf_unmatched.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fasta-read-name-lists",
+                "tests/data/input1",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                t2,
+                "--unmatched-output",
+                t_unmatched,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t_unmatched])
+            .stdout().is(">random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+    }
+
+    #[test]
+    fn test_fastq_exclude_list(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--exclude-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_2 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n\
+                          @random_sequence_length_5_3 4\n\
+                          TCTAC\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_fasta_include_and_exclude_lists(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fasta-read-name-lists",
+                "tests/data/input1",
+                "--exclude-lists",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                t2,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n").unwrap();
+        Assert::command(&["cat",t2])
+            .stdout().is(">random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+    }
+
+    #[test]
+    fn test_match_mode_full_header(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_full_header",
+                "--match-mode",
+                "full-header",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_2 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n").unwrap();
+    }
+
+    #[test]
+    fn test_match_mode_prefix(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_prefix",
+                "--match-mode",
+                "prefix",
+                "--output-fasta-files",
+                t,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_2\n\
+                          TTATG\n\
+                          >random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+    }
+
+    #[test]
+    fn test_match_mode_regex(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_regex",
+                "--match-mode",
+                "regex",
+                "--output-fasta-files",
+                t,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+    }
+
+    #[test]
+    fn test_match_mode_regex_ignore_case(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_regex_upper",
+                "--match-mode",
+                "regex",
+                "--ignore-case",
+                "--output-fasta-files",
+                t,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+    }
+
+    #[test]
+    fn test_match_mode_regex_strip_mate_suffix(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_regex",
+                "--match-mode",
+                "regex",
+                "--strip-mate-suffix",
+                "--output-fasta-files",
+                t,
+                "--input-fasta",
+                "tests/data/a.fasta"])
+            .fails_with(2)
+            .stderr().contains("Names cannot be stripped with the regex match mode")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_strip_mate_suffix(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_both_mates",
+                "--strip-mate-suffix",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/paired_2.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1/2\n\
+                          CCCTA\n\
+                          +\n\
+                          AAAAB\n").unwrap();
+    }
+
+    #[test]
+    fn test_strip_mate_suffix_both_mates_in_input(){
+        // Whether the list names the pair or both its mates, both mates
+        // match the one normalised name
+        for list in &["tests/data/input1", "tests/data/input_both_mates"] {
+            let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+            let t = tf.path().to_str().unwrap();
+            Assert::main_binary()
+                .with_args(&[
+                    "--sequence-name-lists",
+                    list,
+                    "--strip-mate-suffix",
+                    "--output-fastq-files",
+                    t,
+                    "--input-fastq",
+                    "tests/data/interleaved.fq"]).succeeds().unwrap();
+            Assert::command(&["zcat",t])
+                .stdout().is("@random_sequence_length_5_1/1\n\
+                              TAGGG\n\
+                              +\n\
+                              AAAAA\n\
+                              @random_sequence_length_5_1/2\n\
+                              CCCTA\n\
+                              +\n\
+                              AAAAB\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_strip_mate_suffix_missing_name(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_with_missing",
+                "--strip-mate-suffix",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/interleaved.fq"])
+            .fails_with(3)
+            .stderr().contains("2 name(s) from tests/data/input_with_missing were not found")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_ignore_case(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_upper",
+                "--ignore-case",
+                "--output-fasta-files",
+                t,
+                "--output-uncompressed",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["cat",t])
+            .stdout().is(">random_sequence_length_5_2\n\
+                          TTATG\n").unwrap();
+    }
+
+    #[test]
+    fn test_strip_after_empty_delimiter(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--strip-after",
+                "",
+                "--output-fasta-files",
+                t,
+                "--input-fasta",
+                "tests/data/a.fasta"])
+            .fails_with(2)
+            .stderr().contains("The delimiter to strip names after cannot be empty")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_strip_after_delimiter(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--match-mode",
+                "full-header",
+                "--strip-after",
+                " ",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_report_tsv(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let td = tempfile::TempDir::new().unwrap();
+        let report = td.path().join("report.tsv");
+        let report_path = report.to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--report",
+                report_path,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(report_path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(
+            format!("list\toutputs\tpolarity\texpected_names\trecords\tbases\tmissing\tfiltered\tinput\n\
+                     tests/data/input1\t{}\tinclude\t1\t1\t5\t0\t0\tNA\n\
+                     *input*\tNA\tNA\tNA\t3\t15\tNA\t0\tNA\n\
+                     *total*\tNA\tNA\t1\t1\t5\t0\t0\tNA\n", t),
+            contents);
+    }
+
+    #[test]
+    fn test_report_json(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf_unmatched: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t_unmatched = tf_unmatched.path().to_str().unwrap();
+        let tf_report: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t_report = tf_report.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fasta-read-name-lists",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                "--unmatched-output",
+                t_unmatched,
+                "--report",
+                t_report,
+                "--report-format",
+                "json",
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        let report: serde_json::Value = serde_json::from_reader(
+            std::fs::File::open(t_report).unwrap()).unwrap();
+        assert_eq!(report["lists"][0]["list"], "tests/data/input2");
+        assert_eq!(report["lists"][0]["outputs"][0], t);
+        assert_eq!(report["lists"][0]["expected_names"], 2);
+        assert_eq!(report["lists"][0]["records"], 2);
+        assert_eq!(report["lists"][0]["bases"], 10);
+        assert_eq!(report["lists"][0]["missing"], 0);
+        assert_eq!(report["unmatched"]["records"], 1);
+        assert_eq!(report["totals"]["input_records"], 3);
+        assert_eq!(report["totals"]["records_written"], 2);
+    }
+
+    #[test]
+    fn test_missing_names_in_error(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input_with_missing",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(3)
+            .stderr().contains("2 name(s) from tests/data/input_with_missing were not found: \
+                                not_in_the_input_a, not_in_the_input_b")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_missing_names_dir(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        let td = tempfile::TempDir::new().unwrap();
+        let dir = td.path().join("missing");
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input_with_missing",
+                "tests/data/input2",
+                "--output-fastq-files",
+                t,
+                t2,
+                "--missing-names-dir",
+                dir.to_str().unwrap(),
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails()
+            .unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(dir.join("input_with_missing.missing.txt")).unwrap()
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!("not_in_the_input_a\nnot_in_the_input_b\n", contents);
+        contents.clear();
+        std::fs::File::open(dir.join("input2.missing.txt")).unwrap()
+            .read_to_string(&mut contents).unwrap();
+        assert_eq!("", contents);
+    }
+
+    #[test]
+    fn test_on_missing_warn(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input_with_missing",
+                "--output-fastq-files",
+                t,
+                "--on-missing",
+                "warn",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .succeeds()
+            .stderr().contains("Mismatching numbers of read names were observed")
+            .unwrap();
+        Assert::command(&["zcat",t]).stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_on_missing_ignore(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input_with_missing",
+                "--output-fastq-files",
+                t,
+                "--on-missing",
+                "ignore",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .succeeds()
+            .stderr().doesnt_contain("Mismatching")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_missing_list_file_exit_code(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/no_such_list",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(4)
+            .stderr().contains("Failed to open read name file tests/data/no_such_list")
+            .stderr().doesnt_contain("panicked")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_name_exit_code(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input_duplicate",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(7)
+            .stderr().contains("specified twice in input file tests/data/input_duplicate")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_output_count_exit_code(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "tests/data/input2",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("These numbers must be equal")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_detect_fasta_input(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--output-files",
+                t,
+                "--input",
+                "tests/data/a.fasta.gz"]).succeeds().unwrap();
+        Assert::command(&["zcat",t]).stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_2\n\
+                          TTATG\n").unwrap();
+    }
+
+    #[test]
+    fn test_detect_fastq_stdin(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let mut fastq = String::new();
+        std::fs::File::open("tests/data/1.fq").unwrap().read_to_string(&mut fastq).unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-files",
+                t])
+            .stdin(fastq)
+            .succeeds().unwrap();
+        Assert::command(&["zcat",t]).stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_detect_fastq_after_whitespace(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let mut fastq = " \n".to_string();
+        std::fs::File::open("tests/data/1.fq").unwrap().read_to_string(&mut fastq).unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-files",
+                t])
+            .stdin(fastq)
+            .succeeds().unwrap();
+        Assert::command(&["zcat",t]).stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_detect_unrecognised_input(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-files",
+                t,
+                "--input",
+                "tests/data/not_sequences.txt"])
+            .fails_with(5)
+            .stderr().contains("Unrecognised sequence input")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fastq_output_from_fasta_input(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--input",
+                "tests/data/a.fasta"])
+            .fails_with(2)
+            .stderr().contains("FASTQ output cannot be written from FASTA input")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fastq_to_fasta_output(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is(">random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          >random_sequence_length_5_2 2\n\
+                          TTTCA\n").unwrap();
+    }
+
+    #[test]
+    fn test_output_format_by_extension(){
+        let td = tempfile::TempDir::new().unwrap();
+        let fasta = td.path().join("out.fna.gz");
+        let fastq = td.path().join("out.fq.gz");
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "tests/data/input1",
+                "--output-files",
+                fasta.to_str().unwrap(),
+                fastq.to_str().unwrap(),
+                "--input",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",fasta.to_str().unwrap()])
+            .stdout().is(">random_sequence_length_5_1 1\n\
+                          TAGGG\n").unwrap();
+        Assert::command(&["zcat",fastq.to_str().unwrap()])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_output_format_flag(){
+        let td = tempfile::TempDir::new().unwrap();
+        let output = td.path().join("out.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-files",
+                output.to_str().unwrap(),
+                "--output-format",
+                "fasta",
+                "--output-uncompressed",
+                "--input",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(output).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(">random_sequence_length_5_1 1\nTAGGG\n", contents);
+    }
+
+    #[test]
+    fn test_min_mean_quality(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let td = tempfile::TempDir::new().unwrap();
+        let report = td.path().join("report.tsv");
+        let report_path = report.to_str().unwrap();
+        // Read 1 has mean quality 32 and read 2 37.4
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--output-fasta-files",
+                t,
+                "--min-mean-quality",
+                "35",
+                "--report",
+                report_path,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is(">random_sequence_length_5_2 2\n\
+                          TTTCA\n").unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(report_path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(
+            format!("list\toutputs\tpolarity\texpected_names\trecords\tbases\tmissing\tfiltered\tinput\n\
+                     tests/data/input2\t{}\tinclude\t2\t1\t5\t0\t1\tNA\n\
+                     *input*\tNA\tNA\tNA\t3\t15\tNA\t2\tNA\n\
+                     *total*\tNA\tNA\t2\t1\t5\t0\t1\tNA\n", t),
+            contents);
+    }
+
+    #[test]
+    fn test_min_mean_quality_paired(){
+        let td = tempfile::TempDir::new().unwrap();
+        let out1 = td.path().join("out_1.fa");
+        let out2 = td.path().join("out_2.fa");
+        // The mates of pair 1 have mean qualities 32 and 32.2, those of pair 2
+        // both 37.4
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                out1.to_str().unwrap(),
+                "--output-fastq-files-2",
+                out2.to_str().unwrap(),
+                "--min-mean-quality",
+                "35",
+                "--output-uncompressed"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(out1).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(">random_sequence_length_5_2/1\nTTTCA\n", contents);
+        contents.clear();
+        std::fs::File::open(out2).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(">random_sequence_length_5_2/2\nTGAAA\n", contents);
+    }
+
+    #[test]
+    fn test_min_mean_quality_fasta_input(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--output-files",
+                t,
+                "--min-mean-quality",
+                "20",
+                "--input",
+                "tests/data/a.fasta"])
+            .fails_with(2)
+            .stderr().contains("minimum mean quality can only be applied to FASTQ input")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_fastq_sequence_prefix_and_suffix(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--sequence-prefix",
+                "S1_",
+                "--sequence-suffix",
+                "_trimmed",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@S1_random_sequence_length_5_1_trimmed 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_fastq_sequence_prefix_drop_comments(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--sequence-prefix",
+                "S1_",
+                "--drop-comments",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@S1_random_sequence_length_5_1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_fasta_sequence_prefix_keeps_description(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--sequence-prefix",
+                "S1_",
+                "--output-fasta-files",
+                t,
+                "--input-fasta",
+                "tests/data/a_with_desc.fasta"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is(">S1_random_sequence_length_5_1 sample=A\n\
+                          GGTGT\n").unwrap();
+    }
+
+    #[test]
+    fn test_paired_sequence_suffix(){
+        let td = tempfile::TempDir::new().unwrap();
+        let out1 = td.path().join("out_1.fq");
+        let out2 = td.path().join("out_2.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input1",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                out1.to_str().unwrap(),
+                "--output-fastq-files-2",
+                out2.to_str().unwrap(),
+                "--sequence-suffix",
+                ";sample=S1",
+                "--output-uncompressed"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(out2).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_1/2;sample=S1\nCCCTA\n+\nAAAAB\n", contents);
+    }
+
+    #[test]
+    fn test_rename_fastq(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_rename",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@sample_bin_00001 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @sample_bin_00002 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n").unwrap();
+    }
+
+    #[test]
+    fn test_rename_fasta_keep_old_names(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_rename",
+                "tests/data/input1",
+                "--output-fasta-files",
+                t,
+                "/dev/null",
+                "--keep-old-names",
+                "--input-fasta",
+                "tests/data/a_with_desc.fasta"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is(">sample_bin_00001 random_sequence_length_5_1 sample=A\n\
+                          GGTGT\n\
+                          >sample_bin_00002 random_sequence_length_5_2 sample=B\n\
+                          TTATG\n").unwrap();
+    }
+
+    #[test]
+    fn test_rename_paired(){
+        let td = tempfile::TempDir::new().unwrap();
+        let out1 = td.path().join("out_1.fq");
+        let out2 = td.path().join("out_2.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_rename",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                out1.to_str().unwrap(),
+                "--output-fastq-files-2",
+                out2.to_str().unwrap(),
+                "--output-uncompressed"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(out2).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@sample_bin_00001/2\nCCCTA\n+\nAAAAB\n\
+                    @sample_bin_00002/2\nTGAAA\n+\nACGTA\n", contents);
+    }
+
+    #[test]
+    fn test_rename_invalid_new_name(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_rename_invalid",
+                "--output-fastq-files",
+                t,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(5)
+            .stderr().contains("Invalid new name 'sample bin'")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_assignment_table(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("{label}.fa");
+        Assert::main_binary()
+            .with_args(&[
+                "--assignment-table",
+                "tests/data/assignment.tsv",
+                "--output-template",
+                template.to_str().unwrap(),
+                "--output-uncompressed",
+                "--input",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(td.path().join("binA.fa")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(">random_sequence_length_5_1 1\nTAGGG\n>random_sequence_length_5_3 4\nTCTAC\n", contents);
+        contents.clear();
+        std::fs::File::open(td.path().join("binB.fa")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(">random_sequence_length_5_2 2\nTTTCA\n", contents);
+    }
+
+    #[test]
+    fn test_assignment_table_paired(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("{label}_{mate}.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--assignment-table",
+                "tests/data/assignment.tsv",
+                "--output-template",
+                template.to_str().unwrap(),
+                "--output-uncompressed",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(td.path().join("binB_2.fq")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_2/2\nTGAAA\n+\nACGTA\n", contents);
+    }
+
+    #[test]
+    fn test_assignment_table_paired_without_mate(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("{label}.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--assignment-table",
+                "tests/data/assignment.tsv",
+                "--output-template",
+                template.to_str().unwrap(),
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq"])
+            .fails_with(2)
+            .stderr().contains("must contain {mate} for paired input")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_assignment_table_invalid(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("{label}.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--assignment-table",
+                "tests/data/assignment_invalid.tsv",
+                "--output-template",
+                template.to_str().unwrap(),
+                "--input",
+                "tests/data/1.fq"])
+            .fails_with(5)
+            .stderr().contains("Invalid line 'random_sequence_length_5_2' in assignment table")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_output_template(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("out/{stem}.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "-l",
+                "tests/data/input1",
+                "tests/data/input2",
+                "--output-template",
+                template.to_str().unwrap(),
+                "--output-uncompressed",
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(td.path().join("out/input1.fq")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_1 1\nTAGGG\n+\nAAAAA\n", contents);
+        contents.clear();
+        std::fs::File::open(td.path().join("out/input2.fq")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_1 1\nTAGGG\n+\nAAAAA\n\
+                    @random_sequence_length_5_2 2\nTTTCA\n+\nATGCA\n", contents);
+    }
+
+    #[test]
+    fn test_output_template_dir(){
+        let td = tempfile::TempDir::new().unwrap();
+        let list = td.path().join("sample1.txt");
+        std::fs::copy("tests/data/input1", &list).unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "-l",
+                list.to_str().unwrap(),
+                "--output-template",
+                "{dir}/extracted/{list_stem}.fa.gz",
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",td.path().join("extracted/sample1.fa.gz").to_str().unwrap()])
+            .stdout().is(">random_sequence_length_5_1 1\n\
+                          TAGGG\n").unwrap();
+    }
+
+    #[test]
+    fn test_output_template_unknown_placeholder(){
+        Assert::main_binary()
+            .with_args(&[
+                "-l",
+                "tests/data/input1",
+                "--output-template",
+                "out/{label}.fq.gz",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("Unknown placeholder {label} in output template")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_output_template_same_output(){
+        Assert::main_binary()
+            .with_args(&[
+                "-l",
+                "tests/data/input1",
+                "tests/data/input1",
+                "--output-template",
+                "out/{stem}.fq.gz",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("gives the same output out/input1.fq.gz for more than one list")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_output_directory_created(){
+        let td = tempfile::TempDir::new().unwrap();
+        let output = td.path().join("a/b/out.fq.gz");
+        Assert::main_binary()
+            .with_args(&[
+                "-l",
+                "tests/data/input1",
+                "--output-files",
+                output.to_str().unwrap(),
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        assert!(output.exists());
+    }
+
+    #[test]
+    fn test_compressed_lists(){
+        for list in &["tests/data/input2.gz", "tests/data/input2.bz2", "tests/data/input2.zst"] {
+            let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+            let t = tf.path().to_str().unwrap();
+            Assert::main_binary()
+                .with_args(&[
+                    "--sequence-name-lists",
+                    list,
+                    "--output-fastq-files",
+                    t,
+                    "--input-fastq",
+                    "tests/data/1.fq"]).succeeds().unwrap();
+            Assert::command(&["zcat",t])
+                .stdout().is("@random_sequence_length_5_1 1\n\
+                              TAGGG\n\
+                              +\n\
+                              AAAAA\n\
+                              @random_sequence_length_5_2 2\n\
+                              TTTCA\n\
+                              +\n\
+                              ATGCA\n").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_list_from_stdin(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "-",
+                "--output-fastq-files",
+                t1,
+                t2,
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .stdin("random_sequence_length_5_3\n".to_string())
+            .succeeds().unwrap();
+        Assert::command(&["zcat",t2])
+            .stdout().is("@random_sequence_length_5_3 4\n\
+                          TCTAC\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_list_from_stdin_with_stdin_input(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "-",
+                "--output-fastq-files",
+                t])
+            .fails_with(2)
+            .stderr().contains("cannot be read from STDIN when the sequence input is also STDIN")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_multiple_inputs(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let tf_report: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t_report = tf_report.path().to_str().unwrap();
+        // The list has one read from each lane, so is only complete when
+        // both are read
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_1_and_3",
+                "--output-fastq-files",
+                t,
+                "--report",
+                t_report,
+                "--input-fastq",
+                "tests/data/1_L001.fq",
+                "tests/data/1_L002.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @random_sequence_length_5_3 4\n\
+                          TCTAC\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+        let report: serde_json::Value = serde_json::from_reader(
+            std::fs::File::open(t_report).unwrap()).unwrap();
+        assert_eq!(report["inputs"][0]["input"], "tests/data/1_L001.fq");
+        assert_eq!(report["inputs"][0]["records"], 2);
+        assert_eq!(report["inputs"][0]["records_written"][0], 1);
+        assert_eq!(report["inputs"][1]["input"], "tests/data/1_L002.fq");
+        assert_eq!(report["inputs"][1]["records"], 1);
+        assert_eq!(report["inputs"][1]["records_written"][0], 1);
+        assert_eq!(report["totals"]["input_records"], 3);
+    }
+
+    #[test]
+    fn test_multiple_inputs_report_tsv(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let td = tempfile::TempDir::new().unwrap();
+        let report = td.path().join("report.tsv");
+        let report_path = report.to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input_1_and_3",
+                "--output-fastq-files",
+                t,
+                "--report",
+                report_path,
+                "--input",
+                "tests/data/1_L001.fq",
+                "tests/data/1_L002.fq"]).succeeds().unwrap();
+        let mut contents = String::new();
+        std::fs::File::open(report_path).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!(
+            format!("list\toutputs\tpolarity\texpected_names\trecords\tbases\tmissing\tfiltered\tinput\n\
+                     tests/data/input_1_and_3\t{0}\tinclude\t2\t2\t10\t0\t0\tNA\n\
+                     tests/data/input_1_and_3\t{0}\tinclude\tNA\t1\tNA\tNA\tNA\ttests/data/1_L001.fq\n\
+                     tests/data/input_1_and_3\t{0}\tinclude\tNA\t1\tNA\tNA\tNA\ttests/data/1_L002.fq\n\
+                     *input*\tNA\tNA\tNA\t2\t10\tNA\tNA\ttests/data/1_L001.fq\n\
+                     *input*\tNA\tNA\tNA\t1\t5\tNA\tNA\ttests/data/1_L002.fq\n\
+                     *input*\tNA\tNA\tNA\t3\t15\tNA\t0\tNA\n\
+                     *total*\tNA\tNA\t2\t2\t10\t0\t0\tNA\n", t),
+            contents);
+    }
+
+    #[test]
+    fn test_multiple_paired_inputs_mismatched(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t1 = tf1.path().to_str().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t2 = tf2.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                "tests/data/input2",
+                "--input-fastq-1",
+                "tests/data/paired_1.fq",
+                "tests/data/paired_1.fq",
+                "--input-fastq-2",
+                "tests/data/paired_2.fq",
+                "--output-fastq-files-1",
+                t1,
+                "--output-fastq-files-2",
+                t2])
+            .fails_with(2)
+            .stderr().contains("The number of R1 input files was 2, but R2 files there were 1")
+            .unwrap();
+    }
+
+    // Write a FASTQ file of many reads, and a list of every other read name,
+    // so that output spans several compression blocks.
+    fn write_large_fastq(dir: &std::path::Path) -> (String, String) {
+        let fastq = dir.join("large.fq");
+        let list = dir.join("large_list");
+        let mut fastq_writer = std::fs::File::create(&fastq).unwrap();
+        let mut list_writer = std::fs::File::create(&list).unwrap();
+        for i in 0..20000 {
+            let seq: String = (0..100).map(|j| ['A', 'C', 'G', 'T'][(i * 7 + j * 13 + j / 3) % 4]).collect();
+            write!(fastq_writer, "@read{}\n{}\n+\n{}\n", i, seq, "I".repeat(100)).unwrap();
+            if i % 2 == 0 {
+                writeln!(list_writer, "read{}", i).unwrap();
+            }
+        }
+        (fastq.to_str().unwrap().to_string(), list.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_threads(){
+        let td = tempfile::TempDir::new().unwrap();
+        let (fastq, list) = write_large_fastq(td.path());
+        let outputs: Vec<String> = ["t1.fq.gz", "t2.fq.gz", "t4.fq.gz"].iter()
+            .map(|o| td.path().join(o).to_str().unwrap().to_string())
+            .collect();
+        for (threads, output) in ["1", "2", "4"].iter().zip(outputs.iter()) {
+            Assert::main_binary()
+                .with_args(&[
+                    "--sequence-name-lists",
+                    &list,
+                    "--output-fastq-files",
+                    output,
+                    "--threads",
+                    threads,
+                    "--input-fastq",
+                    &fastq]).succeeds().unwrap();
+        }
+        let decompressed: Vec<Vec<u8>> = outputs.iter().map(|o| {
+            std::process::Command::new("zcat").arg(o).output().unwrap().stdout
+        }).collect();
+        assert_eq!(10000 * 4, decompressed[0].iter().filter(|b| **b == b'\n').count());
+        assert_eq!(decompressed[0], decompressed[1]);
+        assert_eq!(decompressed[0], decompressed[2]);
+        // Blocks are the same for any number of threads above one, but one
+        // thread writes a single gzip member
+        assert_eq!(std::fs::read(&outputs[1]).unwrap(), std::fs::read(&outputs[2]).unwrap());
+        assert_ne!(std::fs::read(&outputs[0]).unwrap(), std::fs::read(&outputs[1]).unwrap());
+    }
+
+    #[test]
+    fn test_threads_empty_output(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let mut list: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(list, "not_in_the_input").unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                list.path().to_str().unwrap(),
+                "--output-fastq-files",
+                t,
+                "--threads",
+                "4",
+                "--on-missing",
+                "ignore",
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t]).stdout().is("").unwrap();
+    }
+
+    #[test]
+    fn test_compression_from_extension(){
+        let td = tempfile::TempDir::new().unwrap();
+        let zst = td.path().join("a.fq.zst");
+        let plain = td.path().join("b.fq");
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "tests/data/input2",
+                "--output-fastq-files",
+                zst.to_str().unwrap(),
+                plain.to_str().unwrap(),
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zstdcat",zst.to_str().unwrap()])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+        assert_eq!(
+            "@random_sequence_length_5_1 1\n\
+             TAGGG\n\
+             +\n\
+             AAAAA\n\
+             @random_sequence_length_5_2 2\n\
+             TTTCA\n\
+             +\n\
+             ATGCA\n",
+            std::fs::read_to_string(&plain).unwrap());
+    }
+
+    #[test]
+    fn test_compression_bgzf(){
+        let td = tempfile::TempDir::new().unwrap();
+        let out = td.path().join("a.fq.gz");
+        let t = out.to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input2",
+                "--output-fastq-files",
+                t,
+                "--compression",
+                "bgzf",
+                "--threads",
+                "2",
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n\
+                          @random_sequence_length_5_2 2\n\
+                          TTTCA\n\
+                          +\n\
+                          ATGCA\n").unwrap();
+        let bytes = std::fs::read(t).unwrap();
+        // The BC extra field of the first block, and the end of file block
+        assert_eq!(b"BC", &bytes[12..14]);
+        assert_eq!(&[0x1b, 0, 0x03, 0], &bytes[bytes.len() - 12..bytes.len() - 8]);
+    }
+
+    #[test]
+    fn test_compression_xz_level(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--compression",
+                "xz",
+                "--compression-level",
+                "9",
+                "--input-fastq",
+                "tests/data/1.fq"]).succeeds().unwrap();
+        Assert::command(&["xzcat",t])
+            .stdout().is("@random_sequence_length_5_1 1\n\
+                          TAGGG\n\
+                          +\n\
+                          AAAAA\n").unwrap();
+    }
+
+    #[test]
+    fn test_compression_level_out_of_range(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--compression",
+                "zstd",
+                "--compression-level",
+                "23",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("The zstd compression level must be between 1 and 22, but was 23")
+            .unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                t,
+                "--compression",
+                "none",
+                "--compression-level",
+                "1",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("A compression level cannot be given for uncompressed output")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_bgzf_fasta_index(){
+        let td = tempfile::TempDir::new().unwrap();
+        let list = td.path().join("list");
+        std::fs::write(&list, "random_sequence_length_5_1\n\
+                               random_sequence_length_5_2\n\
+                               random_sequence_length_5_3\n").unwrap();
+        let out = td.path().join("a.fa.bgz");
+        let t = out.to_str().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                list.to_str().unwrap(),
+                "--output-fasta-files",
+                t,
+                "--input-fasta",
+                "tests/data/a.fasta"]).succeeds().unwrap();
+        Assert::command(&["zcat",t])
+            .stdout().is(">random_sequence_length_5_1\n\
+                          GGTGT\n\
+                          >random_sequence_length_5_2\n\
+                          TTATG\n\
+                          >random_sequence_length_5_3\n\
+                          GACCT\n").unwrap();
+        assert_eq!(
+            "random_sequence_length_5_1\t5\t28\t5\t6\n\
+             random_sequence_length_5_2\t5\t62\t5\t6\n\
+             random_sequence_length_5_3\t5\t96\t5\t6\n",
+            std::fs::read_to_string(td.path().join("a.fa.bgz.fai")).unwrap());
+        // A single block, which is not listed
+        assert_eq!(vec![0u8; 8], std::fs::read(td.path().join("a.fa.bgz.gzi")).unwrap());
+    }
+
+    #[test]
+    fn test_bgzf_fastq_index(){
+        let td = tempfile::TempDir::new().unwrap();
+        let (fastq, list) = write_large_fastq(td.path());
+        let out = td.path().join("large.fq.bgz");
+        Assert::main_binary()
+            .with_args(&[
+                "--sequence-name-lists",
+                &list,
+                "--output-fastq-files",
+                out.to_str().unwrap(),
+                "--threads",
+                "2",
+                "--input-fastq",
+                &fastq]).succeeds().unwrap();
+        assert!(!td.path().join("large.fq.bgz.fai").exists());
+        let bgzf = std::fs::read(&out).unwrap();
+        let gzi = std::fs::read(td.path().join("large.fq.bgz.gzi")).unwrap();
+        let read_u64 = |i: usize| u64::from_le_bytes(std::convert::TryInto::try_into(&gzi[i..i+8]).unwrap());
+        let num_entries = read_u64(0) as usize;
+        assert_eq!(8 + 16 * num_entries, gzi.len());
+        assert!(num_entries > 1);
+        for i in 0..num_entries {
+            // Each entry is the start of a block, after a whole number of
+            // full blocks
+            let compressed = read_u64(8 + 16 * i) as usize;
+            assert_eq!(&[0x1f, 0x8b], &bgzf[compressed..compressed + 2]);
+            assert_eq!((i as u64 + 1) * 0xff00, read_u64(16 + 16 * i));
+        }
+    }
+
+    #[test]
+    fn test_bgzf_append(){
+        let td = tempfile::TempDir::new().unwrap();
+        let out = td.path().join("a.fq.bgz");
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "--output-fastq-files",
+                out.to_str().unwrap(),
+                "--append",
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .fails_with(2)
+            .stderr().contains("Cannot append to BGZF output")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_index_memory_logged(){
+        let tf1: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let tf2: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        Assert::main_binary()
+            .with_args(&[
+                "--fastq-read-name-lists",
+                "tests/data/input1",
+                "tests/data/input2",
+                "--output-fastq-files",
+                tf1.path().to_str().unwrap(),
+                tf2.path().to_str().unwrap(),
+                "--input-fastq",
+                "tests/data/1.fq"])
+            .succeeds()
+            .stderr().contains("The index of 2 distinct names used at most")
+            .unwrap();
+    }
 }
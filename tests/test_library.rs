@@ -0,0 +1,219 @@
+extern crate mfqe;
+extern crate tempfile;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use mfqe::error::MfqeError;
+    use mfqe::extraction::{ExtractionJob, JobInput};
+    use mfqe::name_index::{generate_name_index, MatchMode};
+    use mfqe::normalise::NameNormaliser;
+    use mfqe::report::{ExtractionSummary, InputSummary, OnMissing};
+
+    // Run a job with an inclusion list, an exclusion list and an unmatched
+    // output, returning the summary and the IDs written to each output.
+    fn run_parity_job(
+        input: JobInput,
+        match_mode: MatchMode,
+        normaliser: NameNormaliser,
+        include_list: &str) -> (ExtractionSummary, Vec<Vec<String>>) {
+
+        let td = tempfile::TempDir::new().unwrap();
+        let outputs: Vec<String> = ["included", "excluded", "unmatched"].iter()
+            .map(|o| td.path().join(o).to_str().unwrap().to_string())
+            .collect();
+        let summary = ExtractionJob::builder()
+            .input(input)
+            .include_lists(&[include_list])
+            .exclude_lists(&["tests/data/input1"])
+            .outputs(&outputs[..2])
+            .unmatched_output(outputs[2].clone())
+            .match_mode(match_mode)
+            .normaliser(normaliser)
+            .compress(false)
+            .on_missing(OnMissing::Ignore)
+            .build().unwrap()
+            .run().unwrap();
+        let ids = outputs.iter().map(|o| {
+            let mut contents = String::new();
+            std::fs::File::open(o).unwrap().read_to_string(&mut contents).unwrap();
+            contents.lines()
+                .filter(|l| l.starts_with('@') || l.starts_with('>'))
+                .map(|l| l[1..].split(' ').next().unwrap().to_string())
+                .collect()
+        }).collect();
+        (summary, ids)
+    }
+
+    fn assert_parity(match_mode: MatchMode, normaliser: NameNormaliser, include_list: &str) {
+        let (fastq_summary, fastq_ids) = run_parity_job(
+            JobInput::Fastq(Some("tests/data/1.fq".to_string())),
+            match_mode, normaliser.clone(), include_list);
+        let (fasta_summary, fasta_ids) = run_parity_job(
+            JobInput::Fasta(Some("tests/data/a.fasta".to_string())),
+            match_mode, normaliser, include_list);
+        assert_eq!(fastq_ids, fasta_ids);
+        assert_eq!(fastq_summary.total_input, fasta_summary.total_input);
+        assert_eq!(fastq_summary.total_input_bases, fasta_summary.total_input_bases);
+        assert_eq!(fastq_summary.index_to_observed_count, fasta_summary.index_to_observed_count);
+        assert_eq!(fastq_summary.index_to_observed_bases, fasta_summary.index_to_observed_bases);
+        assert_eq!(fastq_summary.total_unmatched, fasta_summary.total_unmatched);
+        assert_eq!(fastq_summary.entry_observed, fasta_summary.entry_observed);
+    }
+
+    #[test]
+    fn test_fastq_fasta_parity_id(){
+        assert_parity(MatchMode::Id, NameNormaliser::default(), "tests/data/input2");
+        assert_parity(MatchMode::Id, NameNormaliser::default(), "tests/data/input_with_missing");
+    }
+
+    #[test]
+    fn test_fastq_fasta_parity_prefix_and_regex(){
+        assert_parity(MatchMode::Prefix, NameNormaliser::default(), "tests/data/input_prefix");
+        assert_parity(MatchMode::Regex, NameNormaliser::default(), "tests/data/input_regex");
+    }
+
+    #[test]
+    fn test_fastq_fasta_parity_normalised(){
+        let normaliser = NameNormaliser { case_fold: true, ..NameNormaliser::default() };
+        assert_parity(MatchMode::Id, normaliser, "tests/data/input_upper");
+    }
+
+    #[test]
+    fn test_extraction_job_run(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let summary = ExtractionJob::builder()
+            .input(JobInput::Fastq(Some("tests/data/1.fq".to_string())))
+            .include_lists(&["tests/data/input1"])
+            .outputs(&[t])
+            .compress(false)
+            .build().unwrap()
+            .run().unwrap();
+        assert_eq!(3, summary.total_input);
+        assert_eq!(vec![1], summary.index_to_observed_count);
+        assert_eq!(vec![5], summary.index_to_observed_bases);
+        let mut contents = String::new();
+        std::fs::File::open(t).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_1 1\nTAGGG\n+\nAAAAA\n", contents);
+    }
+
+    #[test]
+    fn test_extraction_job_output_count(){
+        let result = ExtractionJob::builder()
+            .include_lists(&["tests/data/input1", "tests/data/input2"])
+            .outputs(&["only_one_output.fq.gz"])
+            .build();
+        match result {
+            Err(MfqeError::Argument(_)) => {},
+            _ => panic!("Expected an argument error"),
+        }
+    }
+
+    #[test]
+    fn test_assignment_table(){
+        let td = tempfile::TempDir::new().unwrap();
+        let template = td.path().join("{label}.fq");
+        let summary = ExtractionJob::builder()
+            .input(JobInput::Fastq(Some("tests/data/1.fq".to_string())))
+            .assignment_table("tests/data/assignment.tsv")
+            .output_template(template.to_str().unwrap())
+            .compress(false)
+            .build().unwrap()
+            .run().unwrap();
+        // Labels are numbered in the order they first appear
+        assert_eq!(vec![2, 1], summary.index_to_observed_count);
+        let mut contents = String::new();
+        std::fs::File::open(td.path().join("binB.fq")).unwrap().read_to_string(&mut contents).unwrap();
+        assert_eq!("@random_sequence_length_5_2 2\nTTTCA\n+\nATGCA\n", contents);
+    }
+
+    #[test]
+    fn test_multiple_inputs(){
+        let tf: tempfile::NamedTempFile = tempfile::NamedTempFile::new().unwrap();
+        let t = tf.path().to_str().unwrap();
+        let summary = ExtractionJob::builder()
+            .inputs(vec![
+                JobInput::Fastq(Some("tests/data/1_L001.fq".to_string())),
+                JobInput::Auto(Some("tests/data/1_L002.fq".to_string()))])
+            .include_lists(&["tests/data/input_1_and_3"])
+            .outputs(&[t])
+            .build().unwrap()
+            .run().unwrap();
+        assert_eq!(3, summary.total_input);
+        assert_eq!(vec![2], summary.index_to_observed_count);
+        assert_eq!(vec![
+            InputSummary {
+                input: "tests/data/1_L001.fq".to_string(),
+                records: 2,
+                bases: 10,
+                index_to_observed_count: vec![1],
+            },
+            InputSummary {
+                input: "tests/data/1_L002.fq".to_string(),
+                records: 1,
+                bases: 5,
+                index_to_observed_count: vec![1],
+            }], summary.inputs);
+    }
+
+    #[test]
+    fn test_mixed_paired_and_single_inputs(){
+        let result = ExtractionJob::builder()
+            .inputs(vec![
+                JobInput::Fastq(Some("tests/data/1.fq".to_string())),
+                JobInput::InterleavedFastq(Some("tests/data/interleaved.fq".to_string()))])
+            .include_lists(&["tests/data/input1"])
+            .outputs(&["out.fq.gz"])
+            .build();
+        match result {
+            Err(MfqeError::Argument(_)) => {},
+            _ => panic!("Expected an argument error"),
+        }
+    }
+
+    #[test]
+    fn test_name_in_many_lists(){
+        // Names in one list, a few lists and more lists than are kept
+        // before switching to a bitset
+        let td = tempfile::TempDir::new().unwrap();
+        let lists: Vec<String> = (0..12).map(|i| {
+            let path = td.path().join(format!("list{}", i));
+            let mut names = vec!["in_all".to_string(), format!("only_in_{}", i)];
+            if i < 3 {
+                names.push("in_first_three".to_string());
+            }
+            std::fs::write(&path, names.join("\n")).unwrap();
+            path.to_str().unwrap().to_string()
+        }).collect();
+        let lists: Vec<&str> = lists.iter().map(|l| l.as_str()).collect();
+        let index = generate_name_index(
+            &lists[..10], &lists[10..], MatchMode::Id, NameNormaliser::default()).unwrap();
+        assert_eq!(14, index.num_entries());
+
+        let mut entries = vec![];
+        let mut destinations = vec![];
+        let mut destinations_of = |name: &str| {
+            index.find(name, name.as_bytes(), &mut entries).unwrap();
+            index.destinations(&entries, &mut destinations);
+            destinations.sort_unstable();
+            destinations.clone()
+        };
+        assert_eq!((0..10).collect::<Vec<usize>>(), destinations_of("in_all"));
+        assert_eq!(vec![0, 1, 2, 10, 11], destinations_of("in_first_three"));
+        assert_eq!(vec![4, 10, 11], destinations_of("only_in_4"));
+        assert_eq!(vec![10], destinations_of("only_in_11"));
+        assert_eq!(vec![10, 11], destinations_of("not_in_any"));
+
+        index.find("in_all", b"in_all", &mut entries).unwrap();
+        assert_eq!("in_all", index.name(entries[0]));
+        let mut indices: Vec<usize> = index.indices(entries[0]).collect();
+        indices.sort_unstable();
+        assert_eq!((0..12).collect::<Vec<usize>>(), indices);
+
+        let missing = index.missing_names(&vec![false; index.num_entries()]);
+        assert_eq!(vec!["in_all", "in_first_three", "only_in_0"], missing[0]);
+        assert_eq!(vec!["in_all", "only_in_11"], missing[11]);
+    }
+}